@@ -1,88 +1,324 @@
-use crate::{voice_channel, State};
-use std::{convert::TryInto, ops::RangeInclusive};
+use crate::{
+    helper,
+    parser::PlayRange,
+    player::{BassPreset, FiltersPreset, QueueIndexError, QueuedTrack},
+    state::LavalinkHost,
+    voice_channel, State,
+};
+use futures::{future, stream, StreamExt};
+use serde::Serialize;
+use std::{convert::TryInto, ops::RangeInclusive, sync::Arc, time::Duration};
 use thiserror::Error;
+use tracing::{instrument, warn};
 use twilight_lavalink::{
-    http::{LoadedTracks, Track},
-    model::{Destroy, Pause, Play, Seek, Volume},
+    client::ClientErrorType,
+    http::{LoadType, LoadedTracks, Track},
+    model::{Destroy, Equalizer, EqualizerBand, Pause, Play, Seek, Stop, Volume},
+    node::{Node, NodeConfig},
+    player::Player,
 };
-use twilight_model::id::{ChannelId, GuildId};
+use twilight_model::id::{ChannelId, GuildId, UserId};
 
+#[instrument(skip(state))]
+pub async fn join(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<(), anyhow::Error> {
+    // Join channel.
+    join_voice(state, guild_id, channel_id).await?;
+
+    // Report success.
+    Ok(())
+}
+
+/// Move to another voice channel. The player is kept, so the playback
+/// continues there.
+#[instrument(skip(state))]
+pub async fn move_to(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<(), anyhow::Error> {
+    // Check we're connected.
+    if state.per_guild_data.get_voice_channel(guild_id).is_none() {
+        return Err(NotConnected.into());
+    }
+
+    // Switch channel.
+    join_voice(state, guild_id, channel_id).await?;
+
+    // Report success.
+    Ok(())
+}
+
+#[instrument(skip(state, identifier), fields(identifier = identifier.as_ref()))]
 pub async fn play(
     state: &State,
     guild_id: GuildId,
     channel_id: ChannelId,
     identifier: impl AsRef<str>,
-) -> Result<Track, anyhow::Error> {
+    requested_by: UserId,
+    range: PlayRange,
+    replace: bool,
+) -> Result<Played, anyhow::Error> {
     // Join channel.
-    voice_channel::join(&state.shard, guild_id, channel_id).await?;
+    join_voice(state, guild_id, channel_id).await?;
 
     // Select player.
-    let player = state.lavalink.player(guild_id).await?;
-    let node_config = player.node().config();
+    let player = player(state, guild_id).await?;
 
-    // Load tracks.
-    let req = twilight_lavalink::http::load_track(
-        node_config.address,
-        identifier,
-        &node_config.authorization,
-    )?
-    .try_into()?;
-    let res = state.reqwest.execute(req).await?;
-    let loaded = res.json::<LoadedTracks>().await?;
+    // Load the track.
+    let track = load_first_track(state, player.node(), identifier.as_ref()).await?;
 
-    // Determine the track.
-    let mut tracks = loaded.tracks.into_iter();
-    let track = tracks.next().ok_or_else(|| NoTracksFound)?;
+    // Validate the range.
+    check_range(range, track.info.length as i64)?;
+
+    // Queue up behind the current track, unless asked to replace it.
+    let queued_track = QueuedTrack {
+        track: track.clone(),
+        requested_by,
+    };
+    if !replace && state.per_guild_data.current_track(guild_id).is_some() {
+        // The range is only applied to the track started right away.
+        if range != PlayRange::default() {
+            return Err(RangeWhileEnqueuing.into());
+        }
+        state
+            .per_guild_data
+            .with_track_manger(guild_id, |track_manager| {
+                track_manager.enqueue(std::iter::once(queued_track))
+            })?;
+        return Ok(Played::Enqueued(track));
+    }
+
+    // Issue play command.
+    start_track(state, &player, guild_id, &queued_track, range, !replace)?;
+    fade_in(state, &player, guild_id)?;
+
+    // Report success.
+    Ok(Played::Playing(track))
+}
+
+#[instrument(skip(state, track), fields(track = %track.info.identifier))]
+pub async fn play_track(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    track: Track,
+    requested_by: UserId,
+) -> Result<Track, anyhow::Error> {
+    // Join channel.
+    join_voice(state, guild_id, channel_id).await?;
+
+    // Select player.
+    let player = player(state, guild_id).await?;
 
     // Issue play command.
-    player.send(Play::new(guild_id, &track.track, None, None, false))?;
+    let queued_track = QueuedTrack {
+        track: track.clone(),
+        requested_by,
+    };
+    start_track(
+        state,
+        &player,
+        guild_id,
+        &queued_track,
+        PlayRange::default(),
+        false,
+    )?;
+    fade_in(state, &player, guild_id)?;
 
     // Report success.
     Ok(track)
 }
 
+const SEARCH_RESULTS_LIMIT: usize = 5;
+
+#[instrument(skip(state, query), fields(query = query.as_ref()))]
+pub async fn search(
+    state: &State,
+    guild_id: GuildId,
+    query: impl AsRef<str>,
+) -> Result<Vec<Track>, anyhow::Error> {
+    // Select player.
+    let player = player(state, guild_id).await?;
+
+    // Load tracks.
+    let platform = state.per_guild_data.get_search_platform(guild_id);
+    let identifier = format!("{}{}", platform.prefix(), query.as_ref());
+    let loaded = load_tracks(state, player.node(), &identifier).await?;
+
+    // Report success.
+    Ok(loaded
+        .tracks
+        .into_iter()
+        .take(SEARCH_RESULTS_LIMIT)
+        .collect())
+}
+
+#[derive(Debug)]
+pub enum Played {
+    Playing(Track),
+    Enqueued(Track),
+}
+
+#[derive(Debug)]
+pub enum Enqueued {
+    Track(Track),
+    Playlist {
+        name: Option<String>,
+        count: usize,
+        skipped: usize,
+    },
+}
+
+#[instrument(skip(state, identifier), fields(identifier = identifier.as_ref()))]
 pub async fn enqueue(
     state: &State,
     guild_id: GuildId,
     channel_id: ChannelId,
     identifier: impl AsRef<str>,
-) -> Result<Track, anyhow::Error> {
+    requested_by: UserId,
+) -> Result<Enqueued, anyhow::Error> {
     // Join channel.
-    voice_channel::join(&state.shard, guild_id, channel_id).await?;
+    join_voice(state, guild_id, channel_id).await?;
 
     // Select player.
-    let player = state.lavalink.player(guild_id).await?;
-    let node_config = player.node().config();
+    let player = player(state, guild_id).await?;
 
     // Load tracks.
-    let req = twilight_lavalink::http::load_track(
-        node_config.address,
-        identifier,
-        &node_config.authorization,
-    )?
-    .try_into()?;
-    let res = state.reqwest.execute(req).await?;
-    let loaded = res.json::<LoadedTracks>().await?;
+    let loaded = load_tracks(state, player.node(), identifier.as_ref()).await?;
+
+    // Enqueue the whole playlist.
+    if matches!(loaded.load_type, LoadType::PlaylistLoaded) {
+        if loaded.tracks.is_empty() {
+            return Err(NoTracksFound.into());
+        }
+
+        let total = loaded.tracks.len();
+        let skipped = state
+            .per_guild_data
+            .with_track_manger(guild_id, |track_manager| {
+                track_manager.enqueue(loaded.tracks.into_iter().map(|track| QueuedTrack {
+                    track,
+                    requested_by,
+                }))
+            })?;
+
+        // Report success.
+        return Ok(Enqueued::Playlist {
+            name: loaded.playlist_info.name,
+            count: total - skipped,
+            skipped,
+        });
+    }
 
     // Determine the track.
-    let mut tracks = loaded.tracks.into_iter();
-    let track = tracks.next().ok_or_else(|| NoTracksFound)?;
+    let track = first_track(loaded)?;
 
     // Enqueue track.
     state
         .per_guild_data
         .with_track_manger(guild_id, |track_manager| {
-            track_manager.enqueue(std::iter::once(track.clone()));
-        });
+            track_manager.enqueue(std::iter::once(QueuedTrack {
+                track: track.clone(),
+                requested_by,
+            }))
+        })?;
 
     // Report success.
-    Ok(track)
+    Ok(Enqueued::Track(track))
+}
+
+/// Enqueue the already loaded tracks, returning the number of the enqueued
+/// ones.
+#[instrument(skip(state, tracks))]
+pub async fn enqueue_tracks(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    tracks: Vec<Track>,
+    requested_by: UserId,
+) -> Result<usize, anyhow::Error> {
+    // Join channel.
+    join_voice(state, guild_id, channel_id).await?;
+
+    // Enqueue tracks.
+    let total = tracks.len();
+    let skipped = state
+        .per_guild_data
+        .with_track_manger(guild_id, |track_manager| {
+            track_manager.enqueue(tracks.into_iter().map(|track| QueuedTrack {
+                track,
+                requested_by,
+            }))
+        })?;
+
+    // Report success.
+    Ok(total - skipped)
 }
 
+const ENQUEUE_MANY_CONCURRENCY: usize = 4;
+
+/// Load the tracks concurrently and enqueue the loaded ones in the original
+/// order, returning the number of the enqueued tracks, the failed ones and
+/// the ones skipped because the queue is full.
+#[instrument(skip(state, identifiers))]
+pub async fn enqueue_many(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    identifiers: Vec<String>,
+    requested_by: UserId,
+) -> Result<(usize, usize, usize), anyhow::Error> {
+    // Join channel.
+    join_voice(state, guild_id, channel_id).await?;
+
+    // Select player.
+    let player = player(state, guild_id).await?;
+
+    // Load tracks, a few at a time.
+    let total = identifiers.len();
+    let mut loaded: Vec<(usize, Track)> = stream::iter(identifiers.into_iter().enumerate())
+        .map(|(index, identifier)| {
+            let node = player.node();
+            async move {
+                match load_first_track(state, node, &identifier).await {
+                    Ok(track) => Some((index, track)),
+                    Err(err) => {
+                        warn!(message = "unable to load track", %identifier, ?err);
+                        None
+                    }
+                }
+            }
+        })
+        .buffer_unordered(ENQUEUE_MANY_CONCURRENCY)
+        .filter_map(future::ready)
+        .collect()
+        .await;
+    loaded.sort_by_key(|(index, _)| *index);
+
+    // Enqueue tracks.
+    let count = loaded.len();
+    let skipped = state
+        .per_guild_data
+        .with_track_manger(guild_id, |track_manager| {
+            track_manager.enqueue(loaded.into_iter().map(|(_, track)| QueuedTrack {
+                track,
+                requested_by,
+            }))
+        })?;
+
+    // Report success.
+    Ok((count - skipped, total - count, skipped))
+}
+
+#[instrument(skip(state))]
 pub async fn play_from_queue(
     state: &State,
     guild_id: GuildId,
-) -> Result<Option<Track>, anyhow::Error> {
+) -> Result<Option<QueuedTrack>, anyhow::Error> {
     // Get the track from queue.
     let track = state
         .per_guild_data
@@ -91,33 +327,302 @@ pub async fn play_from_queue(
     let track = match track {
         Some(val) => val,
         // No track is in queue.
-        None => return Ok(None),
+        None => {
+            state.per_guild_data.set_current_track(guild_id, None);
+            return Ok(None);
+        }
     };
 
     // Select player.
-    let player = state.lavalink.player(guild_id).await?;
+    let player = player(state, guild_id).await?;
 
     // Issue play command.
-    player.send(Play::new(guild_id, &track.track, None, None, false))?;
+    start_track(
+        state,
+        &player,
+        guild_id,
+        &track,
+        PlayRange::default(),
+        false,
+    )?;
 
     // Report success.
     Ok(Some(track))
 }
 
+/// Play a track related to the one that has just finished, for when the
+/// queue runs out.
+#[instrument(skip(state, finished), fields(finished = %finished.track.info.identifier))]
+pub async fn autoplay(
+    state: &State,
+    guild_id: GuildId,
+    finished: &QueuedTrack,
+) -> Result<Option<QueuedTrack>, anyhow::Error> {
+    // Select player.
+    let player = player(state, guild_id).await?;
+
+    // Search for the similar tracks.
+    let info = &finished.track.info;
+    let platform = state.per_guild_data.get_search_platform(guild_id);
+    let query = format!(
+        "{}{} {}",
+        platform.prefix(),
+        info.title.as_deref().unwrap_or_default(),
+        info.author.as_deref().unwrap_or_default()
+    );
+    let loaded = load_tracks(state, player.node(), query.trim_end()).await?;
+
+    // Avoid repeating the recently played tracks.
+    let history = state.per_guild_data.history(guild_id);
+    let track = loaded.tracks.into_iter().find(|track| {
+        !history
+            .iter()
+            .any(|played| played.info.identifier == track.info.identifier)
+    });
+    let track = match track {
+        Some(val) => QueuedTrack {
+            track: val,
+            requested_by: finished.requested_by,
+        },
+        None => return Ok(None),
+    };
+
+    // Issue play command.
+    start_track(
+        state,
+        &player,
+        guild_id,
+        &track,
+        PlayRange::default(),
+        false,
+    )?;
+
+    // Report success.
+    Ok(Some(track))
+}
+
+#[instrument(skip(state))]
+pub async fn skip(state: &State, guild_id: GuildId) -> Result<Option<QueuedTrack>, anyhow::Error> {
+    // Issue stop command, keeping the player and the voice connection.
+    let player = player(state, guild_id).await?;
+    player.send(Stop::from(guild_id))?;
+    cancel_fade_in(state, &player, guild_id)?;
+
+    // Play the next track from queue.
+    play_from_queue(state, guild_id).await
+}
+
+/// Skip regardless of what the bot thinks is playing, to recover from the
+/// tracks that never reported their end.
+#[instrument(skip(state))]
+pub async fn force_skip(
+    state: &State,
+    guild_id: GuildId,
+) -> Result<Option<QueuedTrack>, anyhow::Error> {
+    // Issue stop command, keeping the player and the voice connection.
+    let player = player(state, guild_id).await?;
+    player.send(Stop::from(guild_id))?;
+    cancel_fade_in(state, &player, guild_id)?;
+
+    // Drop what the track end would have cleaned up.
+    state.per_guild_data.set_now_playing_message(guild_id, None);
+    state.per_guild_data.set_progress_updater(guild_id, None);
+
+    // Play the next track from queue.
+    play_from_queue(state, guild_id).await
+}
+
+/// Go back to the track played before the current one.
+#[instrument(skip(state))]
+pub async fn previous(
+    state: &State,
+    guild_id: GuildId,
+    requested_by: UserId,
+) -> Result<Option<QueuedTrack>, anyhow::Error> {
+    // Check we're connected.
+    if state.per_guild_data.get_voice_channel(guild_id).is_none() {
+        return Err(NotConnected.into());
+    }
+
+    // Put the previous track in front of the queue.
+    let track = match state.per_guild_data.take_previous(guild_id) {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+    state
+        .per_guild_data
+        .with_track_manger(guild_id, |track_manager| {
+            track_manager.enqueue_front(QueuedTrack {
+                track,
+                requested_by,
+            })
+        });
+
+    // Skip to it.
+    skip(state, guild_id).await
+}
+
+#[instrument(skip(state))]
+pub async fn jump(
+    state: &State,
+    guild_id: GuildId,
+    index: usize,
+) -> Result<QueuedTrack, anyhow::Error> {
+    // Take the track, dropping the ones before it.
+    let track = state
+        .per_guild_data
+        .with_track_manger(guild_id, |track_manager| track_manager.jump_to(index))
+        .ok_or(QueueIndexError { index })?;
+
+    // Issue play command, replacing the current track.
+    let player = player(state, guild_id).await?;
+    start_track(
+        state,
+        &player,
+        guild_id,
+        &track,
+        PlayRange::default(),
+        false,
+    )?;
+
+    // Report success.
+    Ok(track)
+}
+
+/// Skip forward to the first queued track with the query in its title.
+#[instrument(skip(state))]
+pub async fn skip_to(
+    state: &State,
+    guild_id: GuildId,
+    query: &str,
+) -> Result<Option<QueuedTrack>, anyhow::Error> {
+    // Take the matching track, dropping the ones before it.
+    let query = query.to_lowercase();
+    let track = state
+        .per_guild_data
+        .with_track_manger(guild_id, |track_manager| {
+            track_manager.skip_to_match(|track| {
+                track
+                    .track
+                    .info
+                    .title
+                    .as_deref()
+                    .map(|title| title.to_lowercase().contains(&query))
+                    .unwrap_or(false)
+            })
+        });
+    let track = match track {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+
+    // Issue play command, replacing the current track.
+    let player = player(state, guild_id).await?;
+    start_track(
+        state,
+        &player,
+        guild_id,
+        &track,
+        PlayRange::default(),
+        false,
+    )?;
+
+    // Report success.
+    Ok(Some(track))
+}
+
+#[instrument(skip(state))]
 pub async fn stop(state: &State, guild_id: GuildId) -> Result<(), anyhow::Error> {
+    // Fade out, if something is audible.
+    let player = player(state, guild_id).await?;
+    state.per_guild_data.set_fader(guild_id, None);
+    if let Some(duration) = state.fade_duration {
+        if state.per_guild_data.current_track(guild_id).is_some() && !player.paused() {
+            let volume = state.per_guild_data.get_volume(guild_id);
+            fade(state, guild_id, volume, 0, duration).await?;
+        }
+    }
+
     // Issue stop command.
-    let player = state.lavalink.player(guild_id).await?;
     player.send(Destroy::from(guild_id))?;
+    state.per_guild_data.set_current_track(guild_id, None);
+    state.per_guild_data.set_idle_timer(guild_id, None);
+    state.per_guild_data.set_progress_updater(guild_id, None);
+
+    // Drop the queue.
+    state
+        .per_guild_data
+        .with_track_manger(guild_id, |track_manager| track_manager.clear());
 
     // Leave the voice channel.
-    voice_channel::leave(&state.shard, guild_id).await?;
+    leave_voice(state, guild_id).await?;
+
+    // Report success.
+    Ok(())
+}
+
+#[instrument(skip(state))]
+pub async fn disconnect(state: &State, guild_id: GuildId) -> Result<(), anyhow::Error> {
+    // Issue destroy command to free the player.
+    let player = player(state, guild_id).await?;
+    player.send(Destroy::from(guild_id))?;
+    state.per_guild_data.set_idle_timer(guild_id, None);
+    state.per_guild_data.set_progress_updater(guild_id, None);
+
+    // Keep the current track at the front of the queue.
+    if let Some(track) = state.per_guild_data.current_track(guild_id) {
+        state
+            .per_guild_data
+            .with_track_manger(guild_id, |track_manager| track_manager.enqueue_front(track));
+        state.per_guild_data.set_current_track(guild_id, None);
+    }
+
+    // Leave the voice channel.
+    leave_voice(state, guild_id).await?;
 
     // Report success.
     Ok(())
 }
 
+#[derive(Debug)]
+pub struct NowPlaying {
+    pub track: QueuedTrack,
+    pub position: i64,
+    pub node: LavalinkHost,
+}
+
+#[instrument(skip(state))]
+pub async fn now_playing(
+    state: &State,
+    guild_id: GuildId,
+) -> Result<Option<NowPlaying>, anyhow::Error> {
+    // Get the current track.
+    let track = match state.per_guild_data.current_track(guild_id) {
+        Some(val) => val,
+        // Nothing is playing.
+        None => return Ok(None),
+    };
+
+    // Read the position and the node from the player.
+    let player = player(state, guild_id).await?;
+    let position = player.position();
+    let address = player.node().config().address;
+    let node = LavalinkHost {
+        name: state.lavalink_names.get(&address).cloned(),
+        address,
+    };
+
+    // Report success.
+    Ok(Some(NowPlaying {
+        track,
+        position,
+        node,
+    }))
+}
+
 const VOLUME_BOUNDS: RangeInclusive<i64> = 0..=1000;
 
+#[instrument(skip(state))]
 pub async fn volume(state: &State, guild_id: GuildId, volume: i64) -> Result<i64, anyhow::Error> {
     // Validate input bounds.
     if !VOLUME_BOUNDS.contains(&volume) {
@@ -129,42 +634,571 @@ pub async fn volume(state: &State, guild_id: GuildId, volume: i64) -> Result<i64
     }
 
     // Issue volume command.
-    let player = state.lavalink.player(guild_id).await?;
+    let player = player(state, guild_id).await?;
+    state.per_guild_data.set_fader(guild_id, None);
     player.send(Volume::from((guild_id, volume)))?;
+    state.per_guild_data.set_volume(guild_id, volume);
 
     // Report success.
     Ok(volume)
 }
 
+#[instrument(skip(state))]
+pub async fn volume_relative(
+    state: &State,
+    guild_id: GuildId,
+    delta: i64,
+) -> Result<i64, anyhow::Error> {
+    // Compute the new volume, keeping it within bounds.
+    let current = state.per_guild_data.get_volume(guild_id);
+    let volume = current
+        .saturating_add(delta)
+        .clamp(*VOLUME_BOUNDS.start(), *VOLUME_BOUNDS.end());
+
+    // Set the volume.
+    self::volume(state, guild_id, volume).await
+}
+
+#[instrument(skip(state))]
+pub async fn equalizer(
+    state: &State,
+    guild_id: GuildId,
+    preset: BassPreset,
+) -> Result<BassPreset, anyhow::Error> {
+    // Issue equalizer command.
+    let player = player(state, guild_id).await?;
+    player.send(equalizer_command(guild_id, preset))?;
+    state.per_guild_data.set_bass_preset(guild_id, preset);
+
+    // Report success.
+    Ok(preset)
+}
+
+fn equalizer_command(guild_id: GuildId, preset: BassPreset) -> Equalizer {
+    let bands = preset
+        .bands()
+        .into_iter()
+        .map(|(band, gain)| EqualizerBand::new(band, gain))
+        .collect();
+    Equalizer::new(guild_id, bands)
+}
+
+#[instrument(skip(state))]
+pub async fn filters(
+    state: &State,
+    guild_id: GuildId,
+    preset: FiltersPreset,
+) -> Result<FiltersPreset, anyhow::Error> {
+    let (speed, pitch, rate) = preset.timescale();
+    timescale(state, guild_id, speed, pitch, rate).await?;
+    state.per_guild_data.set_filters_preset(guild_id, preset);
+
+    // Report success.
+    Ok(preset)
+}
+
+#[instrument(skip(state))]
+pub async fn timescale(
+    state: &State,
+    guild_id: GuildId,
+    speed: f64,
+    pitch: f64,
+    rate: f64,
+) -> Result<(), anyhow::Error> {
+    // Issue filters command.
+    let player = player(state, guild_id).await?;
+    player.send(filters_command(
+        state,
+        guild_id,
+        Timescale { speed, pitch, rate },
+    ))?;
+    Ok(())
+}
+
+/// Lavalink's `filters` op. It replaces all of the player filters, so the
+/// bass boost equalizer is sent along with the timescale.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Filters {
+    op: &'static str,
+    guild_id: GuildId,
+    equalizer: Vec<EqualizerBand>,
+    timescale: Timescale,
+}
+
+#[derive(Debug, Serialize)]
+struct Timescale {
+    speed: f64,
+    pitch: f64,
+    rate: f64,
+}
+
+fn filters_command(state: &State, guild_id: GuildId, timescale: Timescale) -> Filters {
+    let equalizer = equalizer_command(guild_id, state.per_guild_data.get_bass_preset(guild_id));
+    Filters {
+        op: "filters",
+        guild_id,
+        equalizer: equalizer.bands,
+        timescale,
+    }
+}
+
+const FADE_STEPS: u32 = 20;
+
+/// Gradually change the volume of the player.
+#[instrument(skip(state))]
+pub async fn fade(
+    state: &State,
+    guild_id: GuildId,
+    from: i64,
+    to: i64,
+    duration: Duration,
+) -> Result<(), anyhow::Error> {
+    let player = player(state, guild_id).await?;
+    fade_player(&player, guild_id, from, to, duration).await
+}
+
+async fn fade_player(
+    player: &Player,
+    guild_id: GuildId,
+    from: i64,
+    to: i64,
+    duration: Duration,
+) -> Result<(), anyhow::Error> {
+    let mut interval = tokio::time::interval(duration / FADE_STEPS);
+    for step in 0..=i64::from(FADE_STEPS) {
+        interval.tick().await;
+        let volume = from + (to - from) * step / i64::from(FADE_STEPS);
+        player.send(Volume::from((guild_id, volume)))?;
+    }
+    Ok(())
+}
+
+/// Start the just issued track silently and ramp the volume up in
+/// background, if fading is enabled.
+fn fade_in(state: &State, player: &Arc<Player>, guild_id: GuildId) -> Result<(), anyhow::Error> {
+    let duration = match state.fade_duration {
+        Some(val) => val,
+        None => return Ok(()),
+    };
+    player.send(Volume::from((guild_id, 0)))?;
+
+    let player = Arc::clone(player);
+    let volume = state.per_guild_data.get_volume(guild_id);
+    let fader = tokio::spawn(async move {
+        if let Err(err) = fade_player(&player, guild_id, 0, volume, duration).await {
+            warn!(message = "unable to fade in", ?err);
+        }
+    });
+    state.per_guild_data.set_fader(guild_id, Some(fader));
+    Ok(())
+}
+
+/// Stop the fade in, if any, and restore the full volume so that the next
+/// track isn't left quiet.
+fn cancel_fade_in(state: &State, player: &Player, guild_id: GuildId) -> Result<(), anyhow::Error> {
+    if state.fade_duration.is_none() {
+        return Ok(());
+    }
+    state.per_guild_data.set_fader(guild_id, None);
+    let volume = state.per_guild_data.get_volume(guild_id);
+    player.send(Volume::from((guild_id, volume)))?;
+    Ok(())
+}
+
+#[instrument(skip(state))]
 pub async fn seek(
     state: &State,
     guild_id: GuildId,
     position_in_millis: i64,
 ) -> Result<i64, anyhow::Error> {
+    // Get the current track.
+    let track = state
+        .per_guild_data
+        .current_track(guild_id)
+        .ok_or(NothingPlaying)?;
+
+    // Keep the position within the track.
+    let length = track.track.info.length as i64;
+    let position_in_millis = position_in_millis.clamp(0, length);
+
     // Issue seek command.
-    let player = state.lavalink.player(guild_id).await?;
+    let player = player(state, guild_id).await?;
     player.send(Seek::from((guild_id, position_in_millis)))?;
 
     // Report success.
     Ok(position_in_millis)
 }
 
+#[instrument(skip(state))]
+pub async fn seek_relative(
+    state: &State,
+    guild_id: GuildId,
+    delta_millis: i64,
+) -> Result<i64, anyhow::Error> {
+    // Get the current track.
+    let track = state
+        .per_guild_data
+        .current_track(guild_id)
+        .ok_or(NothingPlaying)?;
+
+    // Compute the new position.
+    let player = player(state, guild_id).await?;
+    let length = track.track.info.length as i64;
+    let position = player
+        .position()
+        .saturating_add(delta_millis)
+        .clamp(0, length);
+
+    // Issue seek command.
+    player.send(Seek::from((guild_id, position)))?;
+
+    // Report success.
+    Ok(position)
+}
+
+#[instrument(skip(state))]
 pub async fn pause_toggle(state: &State, guild_id: GuildId) -> Result<bool, anyhow::Error> {
     // Prepare and issue pause toggle command.
-    let player = state.lavalink.player(guild_id).await?;
+    let player = player(state, guild_id).await?;
     let was_paused = player.paused();
     let should_be_paused = !was_paused;
-    player.send(Pause::from((guild_id, should_be_paused)))?;
+    set_paused(state, guild_id, should_be_paused).await?;
     Ok(should_be_paused)
 }
 
+#[instrument(skip(state))]
+pub async fn set_paused(
+    state: &State,
+    guild_id: GuildId,
+    paused: bool,
+) -> Result<(), anyhow::Error> {
+    // Check the current state.
+    let player = player(state, guild_id).await?;
+    match (player.paused(), paused) {
+        (true, true) => return Err(AlreadyPaused.into()),
+        (false, false) => return Err(AlreadyPlaying.into()),
+        _ => {}
+    }
+
+    // Issue pause command.
+    player.send(Pause::from((guild_id, paused)))?;
+    state.per_guild_data.set_paused(guild_id, paused);
+
+    // Cancel the pending auto-resume.
+    state.per_guild_data.set_resume_timer(guild_id, None);
+
+    // Stay around while paused.
+    if paused {
+        state.per_guild_data.set_idle_timer(guild_id, None);
+    }
+
+    // Report success.
+    Ok(())
+}
+
+/// Get the player, telling apart the lack of Lavalink nodes.
+async fn player(state: &State, guild_id: GuildId) -> Result<Arc<Player>, anyhow::Error> {
+    state
+        .lavalink
+        .player(guild_id)
+        .await
+        .map_err(|err| match err.kind() {
+            ClientErrorType::NodesUnconfigured => NoLavalinkNode.into(),
+            _ => err.into(),
+        })
+}
+
+async fn join_voice(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<(), anyhow::Error> {
+    let previous = state
+        .per_guild_data
+        .set_voice_channel(guild_id, Some(channel_id));
+    voice_channel::join(&state.shard, guild_id, channel_id).await?;
+
+    // Say hi when connecting anew, rather than moving or staying around.
+    if previous.is_none() {
+        if let Err(err) = helper::greet(state, guild_id).await {
+            warn!(message = "unable to post the greeting", %guild_id, ?err);
+        }
+    }
+    Ok(())
+}
+
+async fn leave_voice(state: &State, guild_id: GuildId) -> Result<(), anyhow::Error> {
+    state.per_guild_data.set_voice_channel(guild_id, None);
+    voice_channel::leave(&state.shard, guild_id).await?;
+    Ok(())
+}
+
+/// Issue the play command and re-apply the per guild player settings.
+fn start_track(
+    state: &State,
+    player: &Player,
+    guild_id: GuildId,
+    track: &QueuedTrack,
+    range: PlayRange,
+    no_replace: bool,
+) -> Result<(), anyhow::Error> {
+    let start = range.start.map(|val| val as u64);
+    let end = range.end.map(|val| val as u64);
+    player.send(Play::new(
+        guild_id,
+        &track.track.track,
+        start,
+        end,
+        no_replace,
+    ))?;
+    // A paused player stays paused across the tracks, while the tracks are
+    // expected to start playing right away.
+    if player.paused() {
+        player.send(Pause::from((guild_id, false)))?;
+    }
+    player.send(Volume::from((
+        guild_id,
+        state.per_guild_data.get_volume(guild_id),
+    )))?;
+    let bass_preset = state.per_guild_data.get_bass_preset(guild_id);
+    if bass_preset != BassPreset::Off {
+        player.send(equalizer_command(guild_id, bass_preset))?;
+    }
+    let filters_preset = state.per_guild_data.get_filters_preset(guild_id);
+    if filters_preset != FiltersPreset::Off {
+        let (speed, pitch, rate) = filters_preset.timescale();
+        player.send(filters_command(
+            state,
+            guild_id,
+            Timescale { speed, pitch, rate },
+        ))?;
+    }
+
+    state
+        .per_guild_data
+        .set_current_track(guild_id, Some(track.clone()));
+    state
+        .per_guild_data
+        .push_history(guild_id, track.track.clone());
+    // Follow the one who asked for the playing track.
+    state
+        .per_guild_data
+        .set_controller(guild_id, track.requested_by);
+    state.per_guild_data.set_idle_timer(guild_id, None);
+    Ok(())
+}
+
+async fn load_first_track(
+    state: &State,
+    node: &Node,
+    identifier: &str,
+) -> Result<Track, anyhow::Error> {
+    let loaded = load_tracks(state, node, identifier).await?;
+    first_track(loaded)
+}
+
+fn first_track(loaded: LoadedTracks) -> Result<Track, anyhow::Error> {
+    if matches!(loaded.load_type, LoadType::NoMatches) {
+        return Err(NoMatches.into());
+    }
+    Ok(loaded.tracks.into_iter().next().ok_or(NoTracksFound)?)
+}
+
+/// Check that the range, if any, fits into the track.
+fn check_range(range: PlayRange, length: i64) -> Result<(), RangeOutOfBounds> {
+    if range == PlayRange::default() {
+        return Ok(());
+    }
+    let start = range.start.unwrap_or(0);
+    let end = range.end.unwrap_or(length);
+    if start < 0 || start >= end || end > length {
+        return Err(RangeOutOfBounds { length });
+    }
+    Ok(())
+}
+
+async fn load_tracks(
+    state: &State,
+    node: &Node,
+    identifier: &str,
+) -> Result<LoadedTracks, anyhow::Error> {
+    if let Some(loaded) = state.track_cache.get(identifier) {
+        return Ok(loaded);
+    }
+
+    let loaded = load_tracks_with_fallback(state, node, identifier).await?;
+    state
+        .track_cache
+        .insert(identifier.to_owned(), loaded.clone());
+    Ok(loaded)
+}
+
+async fn load_tracks_with_fallback(
+    state: &State,
+    node: &Node,
+    identifier: &str,
+) -> Result<LoadedTracks, anyhow::Error> {
+    let err = match load_tracks_from(state, node.config(), identifier).await {
+        Ok(val) => return Ok(val),
+        // The node is fine, the track itself can't be loaded.
+        Err(err) if err.is::<LoadFailed>() => return Err(err),
+        Err(err) => err,
+    };
+
+    // Retry with the best of the other nodes, if there is one.
+    let fallback = match state.lavalink.best().await {
+        Ok(val) if val.config().address != node.config().address => val,
+        _ => return Err(err),
+    };
+    warn!(
+        message = "unable to load tracks, retrying with another node",
+        node = %node.config().address,
+        fallback = %fallback.config().address,
+        ?err,
+    );
+    load_tracks_from(state, fallback.config(), identifier).await
+}
+
+async fn load_tracks_from(
+    state: &State,
+    node_config: &NodeConfig,
+    identifier: &str,
+) -> Result<LoadedTracks, anyhow::Error> {
+    let req = twilight_lavalink::http::load_track(
+        node_config.address,
+        identifier,
+        &node_config.authorization,
+    )?
+    .try_into()?;
+    let res = state
+        .reqwest
+        .execute(req)
+        .await
+        .map_err(lavalink_request_error)?;
+    let value = res
+        .json::<serde_json::Value>()
+        .await
+        .map_err(lavalink_request_error)?;
+    let exception_message = value
+        .pointer("/exception/message")
+        .and_then(serde_json::Value::as_str)
+        .map(ToOwned::to_owned);
+    let loaded: LoadedTracks = serde_json::from_value(value)?;
+    if matches!(loaded.load_type, LoadType::LoadFailed) {
+        return Err(LoadFailed {
+            message: exception_message.unwrap_or_else(|| "unknown error".to_owned()),
+        }
+        .into());
+    }
+    Ok(loaded)
+}
+
+fn lavalink_request_error(err: reqwest::Error) -> anyhow::Error {
+    if err.is_timeout() {
+        LavalinkTimeout.into()
+    } else {
+        err.into()
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("no tracks found")]
 pub struct NoTracksFound;
 
+#[derive(Debug, Error)]
+#[error("no matches")]
+pub struct NoMatches;
+
+#[derive(Debug, Error)]
+#[error("lavalink failed to load: {message}")]
+pub struct LoadFailed {
+    pub message: String,
+}
+
+#[derive(Debug, Error)]
+#[error("no lavalink node is available")]
+pub struct NoLavalinkNode;
+
+#[derive(Debug, Error)]
+#[error("lavalink request timed out")]
+pub struct LavalinkTimeout;
+
+#[derive(Debug, Error)]
+#[error("nothing is playing")]
+pub struct NothingPlaying;
+
+#[derive(Debug, Error)]
+#[error("not connected to a voice channel")]
+pub struct NotConnected;
+
+#[derive(Debug, Error)]
+#[error("play range must be within the track length {length}, with start before end")]
+pub struct RangeOutOfBounds {
+    pub length: i64,
+}
+
+#[derive(Debug, Error)]
+#[error("play range can't be used when the track is enqueued")]
+pub struct RangeWhileEnqueuing;
+
+#[derive(Debug, Error)]
+#[error("already paused")]
+pub struct AlreadyPaused;
+
+#[derive(Debug, Error)]
+#[error("already playing")]
+pub struct AlreadyPlaying;
+
 #[derive(Debug, Error)]
 #[error("volume value is out of bounds: {value}, must be in {bounds:?}")]
 pub struct VolumeValueOutOfBounds {
     value: i64,
     bounds: RangeInclusive<i64>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loaded(load_type: &str) -> LoadedTracks {
+        serde_json::from_value(serde_json::json!({
+            "loadType": load_type,
+            "playlistInfo": {},
+            "tracks": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn first_track_of_empty_results() {
+        let err = first_track(loaded("SEARCH_RESULT")).unwrap_err();
+        assert!(err.is::<NoTracksFound>());
+    }
+
+    #[test]
+    fn first_track_of_no_matches() {
+        let err = first_track(loaded("NO_MATCHES")).unwrap_err();
+        assert!(err.is::<NoMatches>());
+    }
+
+    #[test]
+    fn check_range_without_range() {
+        assert!(check_range(PlayRange::default(), 1000).is_ok());
+        assert!(check_range(PlayRange::default(), 0).is_ok());
+    }
+
+    #[test]
+    fn check_range_within_track() {
+        let range = |start, end| PlayRange { start, end };
+        assert!(check_range(range(Some(0), None), 1000).is_ok());
+        assert!(check_range(range(Some(500), Some(1000)), 1000).is_ok());
+        assert!(check_range(range(None, Some(500)), 1000).is_ok());
+
+        assert!(check_range(range(Some(1000), None), 1000).is_err());
+        assert!(check_range(range(Some(500), Some(500)), 1000).is_err());
+        assert!(check_range(range(None, Some(1500)), 1000).is_err());
+        assert!(check_range(range(Some(-1), None), 1000).is_err());
+        assert!(check_range(range(Some(0), None), 0).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{action, helper, spawn_with_handle, State};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+use twilight_model::{id::GuildId, voice::VoiceState};
+
+pub fn process_voice_state_update(state: &Arc<State>, voice_state: &VoiceState) {
+    let guild_id = match voice_state.guild_id {
+        Some(val) => val,
+        None => return,
+    };
+
+    match is_alone(state, guild_id) {
+        Some(true) if !state.per_guild_data.get_always_on(guild_id) => {}
+        // Either someone is in the channel with us, we're not in a channel, or
+        // the 24/7 mode keeps us around.
+        _ => {
+            state.per_guild_data.set_leave_timer(guild_id, None);
+            return;
+        }
+    }
+
+    if state.per_guild_data.has_leave_timer(guild_id) {
+        debug!(message = "leave timer is already pending", %guild_id);
+        return;
+    }
+
+    debug!(message = "voice channel is empty, starting leave timer", %guild_id);
+
+    let state2 = Arc::clone(state);
+    let timer = spawn_with_handle(async move {
+        tokio::time::sleep(state2.empty_channel_timeout).await;
+        let _guard = state2.lock_guild(guild_id).await;
+
+        // Detach from the timer slot so that it doesn't abort us.
+        drop(state2.per_guild_data.take_leave_timer(guild_id));
+
+        if is_alone(&state2, guild_id) != Some(true)
+            || state2.per_guild_data.get_always_on(guild_id)
+        {
+            return Ok(());
+        }
+
+        info!(message = "leaving empty voice channel", %guild_id);
+        action::stop(&state2, guild_id).await
+    });
+    state.per_guild_data.set_leave_timer(guild_id, Some(timer));
+}
+
+pub fn start_idle_timer(state: &Arc<State>, guild_id: GuildId) {
+    if state.per_guild_data.is_paused(guild_id) || state.per_guild_data.get_always_on(guild_id) {
+        return;
+    }
+    debug!(message = "nothing to play, starting idle timer", %guild_id);
+
+    let state2 = Arc::clone(state);
+    let timer = spawn_with_handle(async move {
+        // Warn ahead of leaving, if the timeout leaves room for that. Starting
+        // a track aborts the timer, so the warning is not posted then.
+        match state2
+            .idle_warning
+            .filter(|warning| *warning < state2.idle_timeout)
+        {
+            Some(warning) => {
+                tokio::time::sleep(state2.idle_timeout - warning).await;
+                if !is_idle(&state2, guild_id) {
+                    drop(state2.per_guild_data.take_idle_timer(guild_id));
+                    return Ok(());
+                }
+                if let Some(channel_id) =
+                    state2.per_guild_data.get_associated_text_channel(guild_id)
+                {
+                    let message = format!(
+                        "Leaving in {}s due to inactivity, play something to cancel",
+                        warning.as_secs()
+                    );
+                    if let Err(err) = helper::respond_to(&state2, channel_id, &message).await {
+                        warn!(message = "unable to post the inactivity warning", ?err);
+                    }
+                }
+                tokio::time::sleep(warning).await;
+            }
+            None => tokio::time::sleep(state2.idle_timeout).await,
+        }
+        let _guard = state2.lock_guild(guild_id).await;
+
+        // Detach from the timer slot so that it doesn't abort us.
+        drop(state2.per_guild_data.take_idle_timer(guild_id));
+
+        if !is_idle(&state2, guild_id) {
+            return Ok(());
+        }
+
+        info!(message = "leaving voice channel due to inactivity", %guild_id);
+        action::stop(&state2, guild_id).await?;
+
+        if let Some(channel_id) = state2.per_guild_data.get_associated_text_channel(guild_id) {
+            helper::respond_to(&state2, channel_id, "Left due to inactivity").await?;
+        }
+
+        Ok(())
+    });
+    state.per_guild_data.set_idle_timer(guild_id, Some(timer));
+}
+
+/// Check whether nothing is playing, and nothing keeps the bot around.
+fn is_idle(state: &State, guild_id: GuildId) -> bool {
+    state.per_guild_data.current_track(guild_id).is_none()
+        && !state.per_guild_data.is_paused(guild_id)
+        && !state.per_guild_data.get_always_on(guild_id)
+}
+
+/// Check whether the bot is the only one in its voice channel, returns `None`
+/// if the bot is not in a voice channel.
+fn is_alone(state: &State, guild_id: GuildId) -> Option<bool> {
+    let channel_id = state
+        .cache
+        .voice_state(state.user_id, guild_id)?
+        .channel_id?;
+    let others = state
+        .cache
+        .voice_channel_states(channel_id)?
+        .into_iter()
+        .filter(|voice_state| voice_state.user_id != state.user_id)
+        .count();
+    Some(others == 0)
+}
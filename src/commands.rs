@@ -0,0 +1,286 @@
+/// The commands the bot understands, as `(name, args, description)`.
+pub const COMMANDS: &[(&str, &str, &str)] = &[
+    ("join", "", "Join your voice channel"),
+    (
+        "summon",
+        "",
+        "Move to your voice channel, keeping the playback",
+    ),
+    (
+        "play",
+        "<query> | <link> [at <position> | from <position> to <position>]",
+        "Play a track, or queue it if something is playing",
+    ),
+    (
+        "playnow",
+        "<query> | <link> [at <position> | from <position> to <position>]",
+        "Play a track right away, replacing the current one",
+    ),
+    (
+        "search",
+        "<query>",
+        "Search for tracks and pick one to play",
+    ),
+    ("add", "<track>", "Add a track or a playlist to the queue"),
+    ("addmany", "<track>...", "Add several tracks at once"),
+    ("skip", "", "Skip to the next track in the queue"),
+    ("previous", "", "Go back to the previous track"),
+    (
+        "forceskip",
+        "",
+        "Skip the current track even if it looks stuck",
+    ),
+    (
+        "stop",
+        "",
+        "Stop playing, clear the queue and leave the voice channel",
+    ),
+    (
+        "leave",
+        "",
+        "Leave the voice channel, keeping the queue for the next play",
+    ),
+    (
+        "jump",
+        "<position>",
+        "Skip to a track in the queue, dropping the ones before it",
+    ),
+    (
+        "skipto",
+        "<title>",
+        "Skip to the first queued track with the title",
+    ),
+    ("queue", "", "List the queued tracks"),
+    ("save", "<name>", "Save the queue as a playlist"),
+    (
+        "load",
+        "<name>",
+        "Add the tracks of a saved playlist to the queue",
+    ),
+    ("playlists", "", "List the saved playlists"),
+    ("deleteplaylist", "<name>", "Delete a saved playlist"),
+    ("history", "", "List the recently played tracks"),
+    ("replay", "", "Queue the last played track to play next"),
+    ("dedupe", "", "Remove the duplicate tracks from the queue"),
+    ("clear", "", "Remove all the tracks from the queue"),
+    ("remove", "<position>", "Remove a track from the queue"),
+    ("move", "<from> <to>", "Move a track within the queue"),
+    (
+        "swap",
+        "<position> <position>",
+        "Swap two tracks in the queue",
+    ),
+    ("bump", "<position>", "Move a track to the top of the queue"),
+    ("shuffle", "", "Shuffle the queue"),
+    ("loop", "<off|track|queue>", "Set the loop mode"),
+    ("nowplaying", "", "Show the current track and position"),
+    ("pause", "", "Pause the playback"),
+    ("resume", "", "Resume the playback"),
+    ("pausetoggle", "", "Pause or resume the playback"),
+    (
+        "pausefor",
+        "<seconds>",
+        "Pause the playback and resume it after the given time",
+    ),
+    (
+        "seek",
+        "<millis|mm:ss|hh:mm:ss>",
+        "Seek to the position in the current track",
+    ),
+    ("forward", "<seconds>", "Seek forward in the current track"),
+    ("rewind", "<seconds>", "Seek backward in the current track"),
+    ("restart", "", "Play the current track from the beginning"),
+    ("volume", "[value|+delta|-delta]", "Show or set the volume"),
+    ("bassboost", "<off|low|medium|high>", "Set the bass boost"),
+    (
+        "filters",
+        "<nightcore|vaporwave|off>",
+        "Set the speed and pitch preset",
+    ),
+    (
+        "setprefix",
+        "<prefix>",
+        "Set the command prefix for this server",
+    ),
+    (
+        "setgreeting",
+        "<text>",
+        "Set the message posted when joining a voice channel",
+    ),
+    ("lyrics", "", "Show the lyrics of the current track"),
+    ("grab", "", "Send the current track to you in DMs"),
+    (
+        "searchplatform",
+        "[youtube|soundcloud]",
+        "Show or set where plain queries search",
+    ),
+    (
+        "announcements",
+        "<on|off>",
+        "Announce the tracks as they start and end",
+    ),
+    (
+        "follow",
+        "<on|off>",
+        "Move along with whoever played a track last",
+    ),
+    (
+        "247",
+        "<on|off>",
+        "Stay in the voice channel even when idle or alone",
+    ),
+    (
+        "autoplay",
+        "<on|off>",
+        "Keep playing related tracks when the queue runs out",
+    ),
+    (
+        "samechannel",
+        "<on|off>",
+        "Require being in the bot's voice channel to control the playback",
+    ),
+    ("cachestats", "", "Show the track lookup cache statistics"),
+    ("stats", "", "Show the Lavalink node load"),
+    ("reconnect", "", "Reconnect to the Lavalink nodes"),
+    ("ping", "", "Show the gateway and Lavalink latency"),
+    ("help", "", "Show this help"),
+];
+
+/// The commands that require the DJ role.
+pub const DJ_COMMANDS: &[&str] = &[
+    "stop",
+    "clear",
+    "skip",
+    "forceskip",
+    "previous",
+    "jump",
+    "skipto",
+    "volume",
+    "remove",
+    "move",
+    "swap",
+    "bump",
+    "247",
+    "follow",
+    "deleteplaylist",
+];
+
+/// The commands that require being in the bot's voice channel.
+pub const SAME_CHANNEL_COMMANDS: &[&str] = &["play", "playnow", "skip", "previous", "stop"];
+
+/// The commands only the bot owners can use.
+pub const OWNER_COMMANDS: &[&str] = &["reconnect", "cachestats"];
+
+/// Show how to invoke the command, with the arguments it takes.
+pub fn usage(prefix: &str, command: &str) -> String {
+    let args = COMMANDS
+        .iter()
+        .find(|(name, _, _)| *name == command)
+        .map(|(_, args, _)| *args)
+        .unwrap_or_default();
+    format!("{}{} {}", prefix, command, args)
+        .trim_end()
+        .to_owned()
+}
+
+/// The alternative names of the commands, as `(alias, command)`.
+pub const ALIASES: &[(&str, &str)] = &[
+    ("move-here", "summon"),
+    ("p", "play"),
+    ("enqueue", "add"),
+    ("back", "previous"),
+    ("disconnect", "leave"),
+    ("q", "queue"),
+    ("clean", "dedupe"),
+    ("move-to-top", "bump"),
+    ("np", "nowplaying"),
+];
+
+/// Turn the alias into the command it stands for, other commands are passed
+/// through as is.
+pub fn resolve_alias(command: &str) -> &str {
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == command)
+        .map(|(_, command)| *command)
+        .unwrap_or(command)
+}
+
+/// The farthest a mistyped command can be from a known one to suggest it.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Find the known command closest to the unknown one, if any is close
+/// enough to be what was meant.
+pub fn suggest(command: &str) -> Option<&'static str> {
+    let len = command.chars().count();
+    COMMANDS
+        .iter()
+        .map(|(name, _, _)| (*name, edit_distance(command, name)))
+        .filter(|(_, distance)| *distance <= MAX_SUGGESTION_DISTANCE && *distance < len)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(name, _)| name)
+}
+
+/// The Levenshtein distance between the strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            let insertion = current[j] + 1;
+            let deletion = previous[j + 1] + 1;
+            current.push(substitution.min(insertion).min(deletion));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases() {
+        assert_eq!(resolve_alias("p"), "play");
+        assert_eq!(resolve_alias("np"), "nowplaying");
+        assert_eq!(resolve_alias("enqueue"), "add");
+        assert_eq!(resolve_alias("play"), "play");
+        assert_eq!(resolve_alias("unknown"), "unknown");
+    }
+
+    #[test]
+    fn aliases_resolve_to_commands() {
+        for (alias, command) in ALIASES {
+            assert!(
+                COMMANDS.iter().any(|(name, _, _)| name == command),
+                "{} -> {}",
+                alias,
+                command
+            );
+            assert!(
+                COMMANDS.iter().all(|(name, _, _)| name != alias),
+                "{}",
+                alias
+            );
+        }
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("play", "play"), 0);
+        assert_eq!(edit_distance("ply", "play"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "skip"), 4);
+    }
+
+    #[test]
+    fn suggestions() {
+        assert_eq!(suggest("ply"), Some("play"));
+        assert_eq!(suggest("qeue"), Some("queue"));
+        assert_eq!(suggest("xyzzy"), None);
+        assert_eq!(suggest("a"), None);
+    }
+}
@@ -0,0 +1,115 @@
+use dashmap::DashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use twilight_model::id::{GuildId, UserId};
+
+/// The cooldowns applied when none are configured.
+pub const DEFAULT_COOLDOWNS: &str = "play=3,skip=3";
+
+/// Per-command cooldowns, tracked for every member separately.
+#[derive(Debug, Default)]
+pub struct Cooldowns {
+    windows: HashMap<String, Duration>,
+    last_invoked: DashMap<(GuildId, UserId, String), Instant>,
+}
+
+impl Cooldowns {
+    pub fn new(windows: HashMap<String, Duration>) -> Self {
+        Self {
+            windows,
+            last_invoked: DashMap::new(),
+        }
+    }
+
+    /// Record the invocation, or return the time left to wait if the command
+    /// is still cooling down for the member.
+    pub fn check(&self, guild_id: GuildId, user_id: UserId, command: &str) -> Option<Duration> {
+        let window = *self.windows.get(command)?;
+        let key = (guild_id, user_id, command.to_owned());
+        if let Some(last_invoked) = self.last_invoked.get(&key) {
+            let elapsed = last_invoked.elapsed();
+            if elapsed < window {
+                return Some(window - elapsed);
+            }
+        }
+        self.last_invoked.insert(key, Instant::now());
+        None
+    }
+
+    /// Forget the invocations whose cooldown has passed.
+    pub fn prune(&self) {
+        let windows = &self.windows;
+        self.last_invoked.retain(|(_, _, command), last_invoked| {
+            windows
+                .get(command)
+                .map(|window| last_invoked.elapsed() < *window)
+                .unwrap_or(false)
+        });
+    }
+}
+
+/// Parse the cooldown windows in `command=seconds,...` format.
+pub fn parse_windows(input: &str) -> Result<HashMap<String, Duration>, ParseCooldownsError> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (command, seconds) = entry.split_once('=').ok_or(ParseCooldownsError)?;
+            let seconds = seconds.trim().parse().map_err(|_| ParseCooldownsError)?;
+            Ok((command.trim().to_owned(), Duration::from_secs(seconds)))
+        })
+        .collect()
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error("cooldowns must be in command=seconds,... format")]
+pub struct ParseCooldownsError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let windows = parse_windows("play=3, skip = 5,").unwrap();
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows["play"], Duration::from_secs(3));
+        assert_eq!(windows["skip"], Duration::from_secs(5));
+        assert_eq!(parse_windows("").unwrap().len(), 0);
+        assert_eq!(parse_windows("play"), Err(ParseCooldownsError));
+        assert_eq!(parse_windows("play=x"), Err(ParseCooldownsError));
+    }
+
+    #[test]
+    fn cooling_down() {
+        let cooldowns = Cooldowns::new(parse_windows("skip=60").unwrap());
+        let guild_id = GuildId::new(1).unwrap();
+        let user_id = UserId::new(2).unwrap();
+        let other_user_id = UserId::new(3).unwrap();
+
+        assert_eq!(cooldowns.check(guild_id, user_id, "skip"), None);
+        assert!(cooldowns.check(guild_id, user_id, "skip").is_some());
+        assert_eq!(cooldowns.check(guild_id, other_user_id, "skip"), None);
+        assert_eq!(cooldowns.check(guild_id, user_id, "play"), None);
+        assert_eq!(cooldowns.check(guild_id, user_id, "play"), None);
+    }
+
+    #[test]
+    fn prune() {
+        let cooldowns = Cooldowns::new(parse_windows("skip=60,play=0").unwrap());
+        let guild_id = GuildId::new(1).unwrap();
+        let user_id = UserId::new(2).unwrap();
+
+        assert_eq!(cooldowns.check(guild_id, user_id, "skip"), None);
+        assert_eq!(cooldowns.check(guild_id, user_id, "play"), None);
+        assert_eq!(cooldowns.last_invoked.len(), 2);
+
+        cooldowns.prune();
+        assert_eq!(cooldowns.last_invoked.len(), 1);
+        assert!(cooldowns.check(guild_id, user_id, "skip").is_some());
+    }
+}
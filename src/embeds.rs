@@ -0,0 +1,125 @@
+use crate::{
+    commands::{self, ALIASES, COMMANDS},
+    format_progress,
+    player::QueuedTrack,
+};
+use twilight_lavalink::http::Track;
+use twilight_model::channel::embed::{Embed, EmbedField};
+
+pub fn track(heading: &str, track: &Track) -> Embed {
+    let url = if track.info.uri.is_empty() {
+        None
+    } else {
+        Some(track.info.uri.clone())
+    };
+
+    Embed {
+        description: Some(heading.to_owned()),
+        fields: vec![EmbedField {
+            inline: true,
+            name: "Author".to_owned(),
+            value: track
+                .info
+                .author
+                .clone()
+                .unwrap_or_else(|| "Unknown".to_owned()),
+        }],
+        title: Some(
+            track
+                .info
+                .title
+                .clone()
+                .unwrap_or_else(|| "Unknown".to_owned()),
+        ),
+        url,
+        ..empty()
+    }
+}
+
+pub fn queued_track(heading: &str, queued_track: &QueuedTrack) -> Embed {
+    let mut embed = track(heading, &queued_track.track);
+    embed.fields.push(EmbedField {
+        inline: true,
+        name: "Requested by".to_owned(),
+        value: format!("<@{}>", queued_track.requested_by),
+    });
+    embed
+}
+
+pub fn now_playing(queued_track: &QueuedTrack, position: i64) -> Embed {
+    let mut embed = self::queued_track("Playing", queued_track);
+    embed.fields.push(EmbedField {
+        inline: false,
+        name: "Progress".to_owned(),
+        value: format_progress(position, queued_track.track.info.length as i64),
+    });
+    embed
+}
+
+pub fn lyrics(track: &Track, page: String, index: usize, count: usize) -> Embed {
+    let title = track.info.title.as_deref().unwrap_or("Unknown");
+    let title = if count > 1 {
+        format!("{} ({}/{})", title, index, count)
+    } else {
+        title.to_owned()
+    };
+
+    Embed {
+        description: Some(page),
+        title: Some(title),
+        ..empty()
+    }
+}
+
+pub fn greeting(greeting: &str) -> Embed {
+    Embed {
+        description: Some(greeting.to_owned()),
+        ..empty()
+    }
+}
+
+pub fn help(prefix: &str) -> Embed {
+    let mut lines = vec![format!("Command prefix is `{}`", prefix), String::new()];
+    lines.extend(COMMANDS.iter().map(|(name, _, description)| {
+        let usage = commands::usage(prefix, name);
+        let aliases: Vec<String> = ALIASES
+            .iter()
+            .filter(|(_, command)| command == name)
+            .map(|(alias, _)| format!("`{}{}`", prefix, alias))
+            .collect();
+        if aliases.is_empty() {
+            format!("`{}` - {}", usage, description)
+        } else {
+            format!(
+                "`{}` - {} (also {})",
+                usage,
+                description,
+                aliases.join(", ")
+            )
+        }
+    }));
+
+    Embed {
+        description: Some(lines.join("\n")),
+        title: Some("Commands".to_owned()),
+        ..empty()
+    }
+}
+
+fn empty() -> Embed {
+    Embed {
+        author: None,
+        color: None,
+        description: None,
+        fields: Vec::new(),
+        footer: None,
+        image: None,
+        kind: "rich".to_owned(),
+        provider: None,
+        thumbnail: None,
+        timestamp: None,
+        title: None,
+        url: None,
+        video: None,
+    }
+}
@@ -0,0 +1,44 @@
+use crate::State;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use twilight_gateway::shard::Stage;
+
+pub async fn serve(state: Arc<State>, addr: SocketAddr) -> Result<(), anyhow::Error> {
+    let make_service = make_service_fn(move |_| {
+        let state = Arc::clone(&state);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = Arc::clone(&state);
+                async move { Ok::<_, Infallible>(handle(&state, req).await) }
+            }))
+        }
+    });
+    Server::bind(&addr).serve(make_service).await?;
+    Ok(())
+}
+
+async fn handle(state: &State, req: Request<Body>) -> Response<Body> {
+    let status = match req.uri().path() {
+        "/healthz" => StatusCode::OK,
+        "/readyz" if is_ready(state).await => StatusCode::OK,
+        "/readyz" => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::NOT_FOUND,
+    };
+    let mut res = Response::new(Body::empty());
+    *res.status_mut() = status;
+    res
+}
+
+/// Ready when the gateway is connected and some Lavalink node can take
+/// players.
+async fn is_ready(state: &State) -> bool {
+    let shard_connected = state
+        .shard
+        .info()
+        .map(|info| info.stage() == Stage::Connected)
+        .unwrap_or(false);
+    shard_connected && state.lavalink.best().await.is_ok()
+}
@@ -1,6 +1,11 @@
-use crate::State;
+use crate::{embeds, response_context::truncate_content, State};
+use std::time::Duration;
 use tracing::debug;
-use twilight_model::id::{ChannelId, GuildId, UserId};
+use twilight_model::{
+    channel::ReactionType,
+    gateway::payload::incoming::{MessageCreate, ReactionAdd},
+    id::{ChannelId, GuildId, UserId},
+};
 
 pub async fn user_voice_channel(
     state: &State,
@@ -34,3 +39,98 @@ pub async fn user_voice_channel(
 
     Ok(Some(channel_id))
 }
+
+/// Post the content to the channel on the bot's own initiative, rather than
+/// in reply to a command.
+pub async fn respond_to(
+    state: &State,
+    channel_id: ChannelId,
+    content: &str,
+) -> Result<(), anyhow::Error> {
+    let content = truncate_content(content);
+    state
+        .http
+        .create_message(channel_id)
+        .content(&content)?
+        .exec()
+        .await?;
+    Ok(())
+}
+
+/// Post the guild's greeting to its text channel, unless the announcements
+/// are turned off.
+pub async fn greet(state: &State, guild_id: GuildId) -> Result<(), anyhow::Error> {
+    if !state.per_guild_data.get_announcements(guild_id) {
+        return Ok(());
+    }
+    let channel_id = match state.per_guild_data.get_associated_text_channel(guild_id) {
+        Some(val) => val,
+        None => return Ok(()),
+    };
+
+    let embeds = [embeds::greeting(
+        &state.per_guild_data.get_greeting(guild_id),
+    )];
+    state
+        .http
+        .create_message(channel_id)
+        .embeds(&embeds)?
+        .exec()
+        .await?;
+    Ok(())
+}
+
+/// Ask the member to confirm the stop when it drops a long queue, by replying
+/// yes or reacting to the prompt. Short queues need no confirmation.
+pub async fn confirm_stop(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    user_id: UserId,
+) -> Result<bool, anyhow::Error> {
+    let queue_len = state.per_guild_data.queue_len(guild_id);
+    if queue_len <= state.stop_confirmation_threshold {
+        return Ok(true);
+    }
+
+    let prompt = format!(
+        "This drops {} queued tracks, <@{}> reply yes or react with 👍 to confirm",
+        queue_len, user_id
+    );
+    let prompt = state
+        .http
+        .create_message(channel_id)
+        .content(&prompt)?
+        .exec()
+        .await?
+        .model()
+        .await?;
+
+    let reply = state
+        .standby
+        .wait_for_message(channel_id, move |event: &MessageCreate| {
+            event.author.id == user_id && event.content.trim().eq_ignore_ascii_case("yes")
+        });
+    let reaction = state
+        .standby
+        .wait_for_reaction(prompt.id, move |event: &ReactionAdd| {
+            event.user_id == user_id
+                && matches!(
+                    &event.emoji,
+                    ReactionType::Unicode { name } if name == "👍"
+                )
+        });
+    let confirmation = async {
+        tokio::select! {
+            res = reply => res.is_ok(),
+            res = reaction => res.is_ok(),
+        }
+    };
+    Ok(
+        tokio::time::timeout(STOP_CONFIRMATION_TIMEOUT, confirmation)
+            .await
+            .unwrap_or(false),
+    )
+}
+
+const STOP_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(15);
@@ -0,0 +1,595 @@
+use crate::{
+    action, commands, embeds, enqueue_resolved, format_duration, format_play_heading, format_queue,
+    format_queued_track, format_track,
+    helper::{self, user_voice_channel},
+    parser, permissions, queue_page_count, resolve,
+    response_context::{error_content, truncate_content},
+    spawn, State,
+};
+use std::{sync::Arc, time::Duration};
+use tracing::{debug, info, info_span, warn};
+use twilight_model::{
+    application::{
+        callback::{CallbackData, InteractionResponse},
+        command::{ChoiceCommandOptionData, CommandOption},
+        component::{button::ButtonStyle, ActionRow, Button, Component},
+        interaction::{
+            application_command::CommandDataOption, Interaction, MessageComponentInteraction,
+        },
+    },
+    channel::{embed::Embed, message::MessageFlags},
+    id::{ChannelId, GuildId, InteractionId, MessageId, UserId},
+};
+
+/// The commands that are also available as slash commands.
+const SLASH_COMMANDS: &[&str] = &[
+    "play",
+    "skip",
+    "queue",
+    "stop",
+    "leave",
+    "nowplaying",
+    "pause",
+    "resume",
+    "volume",
+];
+
+/// Register the slash commands with Discord.
+pub async fn register(state: &State) -> Result<(), anyhow::Error> {
+    for name in SLASH_COMMANDS {
+        let description = commands::COMMANDS
+            .iter()
+            .find(|(command, _, _)| command == name)
+            .map(|(_, _, description)| *description)
+            .unwrap_or_default();
+        state
+            .http
+            .create_global_command(name)?
+            .chat_input(description)?
+            .command_options(&options(name))?
+            .exec()
+            .await?;
+    }
+    Ok(())
+}
+
+fn options(name: &str) -> Vec<CommandOption> {
+    match name {
+        "play" => vec![CommandOption::String(ChoiceCommandOptionData {
+            description: "The link or the search query".to_owned(),
+            name: "track".to_owned(),
+            required: true,
+            ..Default::default()
+        })],
+        "volume" => vec![CommandOption::Integer(ChoiceCommandOptionData {
+            description: "The volume to set, leave out to show the current one".to_owned(),
+            name: "value".to_owned(),
+            required: false,
+            ..Default::default()
+        })],
+        _ => Vec::new(),
+    }
+}
+
+/// The buttons attached to the track announcements, as
+/// `(custom id, label, command)`.
+const PLAYBACK_BUTTONS: &[(&str, &str, &str)] = &[
+    ("playback:pausetoggle", "Pause/Resume", "pausetoggle"),
+    ("playback:skip", "Skip", "skip"),
+    ("playback:stop", "Stop", "stop"),
+];
+
+/// The buttons attached to the queue listings, as `(custom id, label)`.
+const QUEUE_BUTTONS: &[(&str, &str)] = &[("queue:previous", "Previous"), ("queue:next", "Next")];
+
+/// How long the queue listing buttons keep working.
+const QUEUE_BUTTONS_TTL: Duration = Duration::from_secs(5 * 60);
+
+pub fn playback_buttons() -> Vec<Component> {
+    button_row(
+        PLAYBACK_BUTTONS
+            .iter()
+            .map(|(custom_id, label, _)| (*custom_id, *label)),
+    )
+}
+
+pub fn queue_buttons() -> Vec<Component> {
+    button_row(QUEUE_BUTTONS.iter().copied())
+}
+
+fn button_row<'a>(buttons: impl Iterator<Item = (&'a str, &'a str)>) -> Vec<Component> {
+    let buttons = buttons
+        .map(|(custom_id, label)| {
+            Component::Button(Button {
+                custom_id: Some(custom_id.to_owned()),
+                disabled: false,
+                emoji: None,
+                label: Some(label.to_owned()),
+                style: ButtonStyle::Secondary,
+                url: None,
+            })
+        })
+        .collect();
+    vec![Component::ActionRow(ActionRow {
+        components: buttons,
+    })]
+}
+
+/// Keep the queue listing message pageable for a while, removing the
+/// buttons afterwards.
+pub fn track_queue_listing(
+    state: &Arc<State>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+) {
+    state.queue_listings.insert(message_id, (guild_id, 0));
+
+    let state = Arc::clone(state);
+    spawn(async move {
+        tokio::time::sleep(QUEUE_BUTTONS_TTL).await;
+        state.queue_listings.remove(&message_id);
+        state
+            .http
+            .update_message(channel_id, message_id)
+            .components(Some(&[]))?
+            .exec()
+            .await?;
+        Ok(())
+    });
+}
+
+pub fn process_interaction(state: &Arc<State>, interaction: &Interaction) {
+    let command = match interaction {
+        Interaction::ApplicationCommand(val) => val,
+        Interaction::MessageComponent(val) => return process_component(state, val),
+        _ => return,
+    };
+    let guild_id = match command.guild_id {
+        Some(val) => val,
+        None => {
+            debug!(message = "skipping non-guild interaction", ?command);
+            return;
+        }
+    };
+    let author_id = match command
+        .member
+        .as_ref()
+        .and_then(|member| member.user.as_ref())
+    {
+        Some(user) => user.id,
+        None => return,
+    };
+
+    let name = command.data.name.clone();
+    let span = info_span!("command", %guild_id, user_id = %author_id, command = %name);
+    let _enter = span.enter();
+    info!(message = "got slash command", options = ?command.data.options);
+    state.metrics.command_processed();
+
+    state
+        .per_guild_data
+        .associate_text_channel(guild_id, command.channel_id);
+    let context = InteractionContext::new(Arc::clone(state), command.id, command.token.clone());
+    let options = command.data.options.clone();
+    let text_channel_id = command.channel_id;
+
+    let state = Arc::clone(state);
+    spawn(async move {
+        // Acknowledge right away, loading the tracks can take longer than
+        // Discord waits for.
+        context.defer().await?;
+
+        if commands::DJ_COMMANDS.contains(&name.as_str())
+            && !(name == "volume" && options.is_empty())
+            && !permissions::is_dj(&state, guild_id, author_id)
+        {
+            context
+                .with_content("You need the DJ role to do that")
+                .await?;
+            return Ok(());
+        }
+        if commands::SAME_CHANNEL_COMMANDS.contains(&name.as_str())
+            && !permissions::in_bot_voice_channel(&state, guild_id, author_id)
+        {
+            context
+                .with_content("You must be in my voice channel to do that")
+                .await?;
+            return Ok(());
+        }
+
+        if let Some(remaining) = state.cooldowns.check(guild_id, author_id, &name) {
+            context
+                .with_content(&format!(
+                    "Slow down, try again in {}s",
+                    remaining.as_secs_f64().ceil()
+                ))
+                .await?;
+            return Ok(());
+        }
+
+        match handle(
+            &state,
+            &context,
+            guild_id,
+            text_channel_id,
+            author_id,
+            &name,
+            &options,
+        )
+        .await
+        {
+            Err(err) => {
+                let content = error_content(&err).unwrap_or_else(|| {
+                    warn!("handler error: {:?}", err);
+                    format!("Something went wrong: {}", err)
+                });
+                context.with_content(&content).await
+            }
+            Ok(()) => Ok(()),
+        }
+    });
+}
+
+fn process_component(state: &Arc<State>, component: &MessageComponentInteraction) {
+    let guild_id = match component.guild_id {
+        Some(val) => val,
+        None => return,
+    };
+    let author_id = match component
+        .member
+        .as_ref()
+        .and_then(|member| member.user.as_ref())
+    {
+        Some(user) => user.id,
+        None => return,
+    };
+    if let Some((custom_id, _)) = QUEUE_BUTTONS
+        .iter()
+        .find(|(custom_id, _)| *custom_id == component.data.custom_id)
+    {
+        return process_queue_button(state, component, *custom_id == "queue:next");
+    }
+    let command = match PLAYBACK_BUTTONS
+        .iter()
+        .find(|(custom_id, _, _)| *custom_id == component.data.custom_id)
+    {
+        Some((_, _, command)) => *command,
+        None => {
+            debug!(message = "skipping unknown component", custom_id = %component.data.custom_id);
+            return;
+        }
+    };
+    let span = info_span!("command", %guild_id, user_id = %author_id, %command);
+    let _enter = span.enter();
+    info!(message = "got button press");
+    state.metrics.command_processed();
+
+    let id = component.id;
+    let token = component.token.clone();
+    let text_channel_id = component.channel_id;
+    let state = Arc::clone(state);
+    spawn(async move {
+        let denial = if commands::DJ_COMMANDS.contains(&command)
+            && !permissions::is_dj(&state, guild_id, author_id)
+        {
+            Some("You need the DJ role to do that")
+        } else if commands::SAME_CHANNEL_COMMANDS.contains(&command)
+            && !permissions::in_bot_voice_channel(&state, guild_id, author_id)
+        {
+            Some("You must be in my voice channel to do that")
+        } else {
+            None
+        };
+        if let Some(denial) = denial {
+            state
+                .http
+                .interaction_callback(id, &token, &ephemeral(denial))
+                .exec()
+                .await?;
+            return Ok(());
+        }
+
+        // Acknowledge before acting, the announcements about the outcome
+        // are posted separately.
+        state
+            .http
+            .interaction_callback(id, &token, &InteractionResponse::DeferredUpdateMessage)
+            .exec()
+            .await?;
+
+        match command {
+            "pausetoggle" => {
+                let _guard = state.lock_guild(guild_id).await;
+                action::pause_toggle(&state, guild_id).await?;
+            }
+            "skip" => {
+                let _guard = state.lock_guild(guild_id).await;
+                action::skip(&state, guild_id).await?;
+            }
+            "stop" => {
+                if !helper::confirm_stop(&state, guild_id, text_channel_id, author_id).await? {
+                    helper::respond_to(&state, text_channel_id, "Stop cancelled").await?;
+                    return Ok(());
+                }
+                let _guard = state.lock_guild(guild_id).await;
+                action::stop(&state, guild_id).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    });
+}
+
+fn process_queue_button(state: &Arc<State>, component: &MessageComponentInteraction, next: bool) {
+    let message_id = component.message.id;
+    let guild_id = component.guild_id;
+    let id = component.id;
+    let token = component.token.clone();
+    let state = Arc::clone(state);
+    spawn(async move {
+        // Recompute the page from the live queue, it might have changed.
+        let content = state
+            .queue_listings
+            .get_mut(&message_id)
+            .map(|mut listing| {
+                let (guild_id, page) = &mut *listing;
+                state
+                    .per_guild_data
+                    .read_track_manager(*guild_id, |track_manager| {
+                        let pages = queue_page_count(track_manager.list().len());
+                        *page = if next {
+                            (*page + 1).min(pages - 1)
+                        } else {
+                            page.saturating_sub(1)
+                        };
+                        format_queue(track_manager.list(), *page)
+                    })
+            });
+        let response = match content {
+            Some(content) => InteractionResponse::UpdateMessage(CallbackData {
+                allowed_mentions: None,
+                components: Some(queue_buttons()),
+                content: Some(content),
+                embeds: Vec::new(),
+                flags: None,
+                tts: None,
+            }),
+            None => {
+                let prefix = guild_id
+                    .map(|guild_id| state.per_guild_data.get_prefix(guild_id))
+                    .unwrap_or_default();
+                ephemeral(&format!(
+                    "These buttons have expired, list the queue again with `{}queue`",
+                    prefix
+                ))
+            }
+        };
+        state
+            .http
+            .interaction_callback(id, &token, &response)
+            .exec()
+            .await?;
+        Ok(())
+    });
+}
+
+/// A response only the user who interacted can see.
+fn ephemeral(content: &str) -> InteractionResponse {
+    InteractionResponse::ChannelMessageWithSource(CallbackData {
+        allowed_mentions: None,
+        components: None,
+        content: Some(content.to_owned()),
+        embeds: Vec::new(),
+        flags: Some(MessageFlags::EPHEMERAL),
+        tts: None,
+    })
+}
+
+async fn handle(
+    state: &State,
+    context: &InteractionContext,
+    guild_id: GuildId,
+    text_channel_id: ChannelId,
+    author_id: UserId,
+    name: &str,
+    options: &[CommandDataOption],
+) -> Result<(), anyhow::Error> {
+    match name {
+        "play" => {
+            let query = options.iter().find_map(|option| match option {
+                CommandDataOption::String { value, .. } => Some(value.clone()),
+                _ => None,
+            });
+            let (args, range) = parser::play_range(parser::tokenize(&query.unwrap_or_default()));
+            let identifier = match parser::track_identifier(
+                args,
+                state.per_guild_data.get_search_platform(guild_id),
+            ) {
+                Some(val) => val,
+                None => {
+                    context.with_content("Pass track as an argument").await?;
+                    return Ok(());
+                }
+            };
+            let channel_id = match user_voice_channel(state, guild_id, author_id).await? {
+                Some(val) => val,
+                None => {
+                    context
+                        .with_content("You need to join a voice channel first")
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let _guard = state.lock_guild(guild_id).await;
+            let mut identifiers = resolve::resolve(state, identifier).await?.into_iter();
+            let identifier = match identifiers.next() {
+                Some(val) => val,
+                None => return context.with_content("No tracks found").await,
+            };
+            match action::play(
+                state, guild_id, channel_id, identifier, author_id, range, false,
+            )
+            .await
+            {
+                Ok(played) => {
+                    let embed = match played {
+                        action::Played::Playing(track) => {
+                            embeds::track(&format_play_heading(range), &track)
+                        }
+                        action::Played::Enqueued(track) => embeds::track("Enqueued", &track),
+                    };
+                    context.with_embed(embed).await?;
+
+                    // Queue the rest of the album or the playlist.
+                    let enqueued =
+                        enqueue_resolved(state, guild_id, channel_id, identifiers, author_id).await;
+                    if enqueued > 0 {
+                        context
+                            .with_content(&format!("Enqueued {} more tracks", enqueued))
+                            .await?;
+                    }
+                    Ok(())
+                }
+                Err(err) => Err(err),
+            }
+        }
+        "skip" => {
+            let _guard = state.lock_guild(guild_id).await;
+            match action::skip(state, guild_id).await? {
+                Some(track) => {
+                    context
+                        .with_content(&format!("Skipped, playing {}", format_track(&track.track)))
+                        .await
+                }
+                None => context.with_content("Nothing left to play").await,
+            }
+        }
+        "queue" => {
+            let content = state
+                .per_guild_data
+                .read_track_manager(guild_id, |track_manager| {
+                    format_queue(track_manager.list(), 0)
+                });
+            context.with_content(&content).await
+        }
+        "stop" => {
+            if !helper::confirm_stop(state, guild_id, text_channel_id, author_id).await? {
+                return context.with_content("Stop cancelled").await;
+            }
+            let _guard = state.lock_guild(guild_id).await;
+            action::stop(state, guild_id).await?;
+            context.with_content("Stopped").await
+        }
+        "leave" => {
+            let _guard = state.lock_guild(guild_id).await;
+            action::disconnect(state, guild_id).await?;
+            context
+                .with_content("Disconnected, the queue is kept")
+                .await
+        }
+        "nowplaying" => match action::now_playing(state, guild_id).await? {
+            Some(now_playing) => {
+                context
+                    .with_content(&format!(
+                        "Playing {} [{} / {}] on node {}",
+                        format_queued_track(&now_playing.track),
+                        format_duration(now_playing.position),
+                        format_duration(now_playing.track.track.info.length as i64),
+                        now_playing.node,
+                    ))
+                    .await
+            }
+            None => context.with_content("Nothing is playing").await,
+        },
+        "pause" | "resume" => {
+            let paused = name == "pause";
+            let _guard = state.lock_guild(guild_id).await;
+            match action::set_paused(state, guild_id, paused).await {
+                Ok(()) => {
+                    context
+                        .with_content(if paused { "Paused" } else { "Resumed" })
+                        .await
+                }
+                Err(err) => Err(err),
+            }
+        }
+        "volume" => {
+            let value = options.iter().find_map(|option| match option {
+                CommandDataOption::Integer { value, .. } => Some(*value),
+                _ => None,
+            });
+            let value = match value {
+                Some(val) => val,
+                None => {
+                    let volume = state.per_guild_data.get_volume(guild_id);
+                    return context
+                        .with_content(&format!("Current volume: {}", volume))
+                        .await;
+                }
+            };
+            match action::volume(state, guild_id, value).await {
+                Ok(val) => {
+                    context
+                        .with_content(&format!("Volume was set to {}", val))
+                        .await
+                }
+                Err(err) => Err(err),
+            }
+        }
+        _ => context.with_content("Unknown command").await,
+    }
+}
+
+/// Like `ResponseContext`, but responds to an interaction.
+#[derive(Debug)]
+struct InteractionContext {
+    state: Arc<State>,
+    id: InteractionId,
+    token: String,
+}
+
+impl InteractionContext {
+    fn new(state: Arc<State>, id: InteractionId, token: String) -> Self {
+        Self { state, id, token }
+    }
+
+    async fn defer(&self) -> Result<(), anyhow::Error> {
+        let response = InteractionResponse::DeferredChannelMessageWithSource(CallbackData {
+            allowed_mentions: None,
+            components: None,
+            content: None,
+            embeds: Vec::new(),
+            flags: None,
+            tts: None,
+        });
+        self.state
+            .http
+            .interaction_callback(self.id, &self.token, &response)
+            .exec()
+            .await?;
+        Ok(())
+    }
+
+    async fn with_content(&self, content: &str) -> Result<(), anyhow::Error> {
+        let content = truncate_content(content);
+        self.state
+            .http
+            .update_interaction_original(&self.token)?
+            .content(Some(&content))?
+            .exec()
+            .await?;
+        Ok(())
+    }
+
+    async fn with_embed(&self, embed: Embed) -> Result<(), anyhow::Error> {
+        let embeds = [embed];
+        self.state
+            .http
+            .update_interaction_original(&self.token)?
+            .embeds(Some(&embeds))?
+            .exec()
+            .await?;
+        Ok(())
+    }
+}
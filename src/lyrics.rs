@@ -0,0 +1,120 @@
+use crate::State;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use thiserror::Error;
+use twilight_lavalink::http::Track;
+
+/// The maximum length of a lyrics page, in characters.
+pub const PAGE_LEN: usize = 2000;
+
+/// Lyrics provider client. The provider is queried with
+/// `GET <base url>/lyrics?title=<title>&artist=<artist>` and is expected to
+/// respond with `{"lyrics": "..."}`, or with 404 if it has none.
+#[derive(Debug)]
+pub struct Lyrics {
+    base_url: String,
+    token: Option<String>,
+}
+
+impl Lyrics {
+    pub fn new(base_url: String, token: Option<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            token,
+        }
+    }
+
+    async fn get(
+        &self,
+        state: &State,
+        title: &str,
+        artist: &str,
+    ) -> Result<Option<String>, anyhow::Error> {
+        let mut req = state
+            .reqwest
+            .get(format!("{}/lyrics", self.base_url))
+            .query(&[("title", title), ("artist", artist)]);
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+
+        let res = req.send().await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let res: LyricsResponse = res.error_for_status()?.json().await?;
+        Ok(res.lyrics.filter(|lyrics| !lyrics.trim().is_empty()))
+    }
+}
+
+/// Look up the lyrics of the track by its title and author.
+pub async fn search(state: &State, track: &Track) -> Result<Option<String>, anyhow::Error> {
+    let lyrics = state.lyrics.as_ref().ok_or(LyricsNotConfigured)?;
+    let title = track.info.title.as_deref().unwrap_or_default();
+    let artist = track.info.author.as_deref().unwrap_or_default();
+    lyrics.get(state, title, artist).await
+}
+
+/// Split the lyrics into pages of at most `page_len` characters, breaking
+/// between the lines where possible.
+pub fn paginate(lyrics: &str, page_len: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    let mut page_chars = 0;
+
+    for line in lyrics.trim().lines() {
+        let line_chars = line.chars().count();
+        if page_chars > 0 && page_chars + 1 + line_chars > page_len {
+            pages.push(std::mem::take(&mut page));
+            page_chars = 0;
+        }
+        if page_chars > 0 {
+            page.push('\n');
+            page_chars += 1;
+        }
+        // Lines that don't fit a page on their own are cut.
+        for c in line.chars() {
+            if page_chars == page_len {
+                pages.push(std::mem::take(&mut page));
+                page_chars = 0;
+            }
+            page.push(c);
+            page_chars += 1;
+        }
+    }
+    if !page.is_empty() {
+        pages.push(page);
+    }
+
+    pages
+}
+
+#[derive(Debug, Deserialize)]
+struct LyricsResponse {
+    lyrics: Option<String>,
+}
+
+#[derive(Debug, Error)]
+#[error("lyrics provider is not configured")]
+pub struct LyricsNotConfigured;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_page() {
+        assert_eq!(paginate("one\ntwo\n", 10), vec!["one\ntwo"]);
+        assert!(paginate("", 10).is_empty());
+    }
+
+    #[test]
+    fn breaks_between_lines() {
+        assert_eq!(paginate("one\ntwo\nthree", 8), vec!["one\ntwo", "three"]);
+    }
+
+    #[test]
+    fn cuts_long_lines() {
+        assert_eq!(paginate("abcdefg\nhi", 3), vec!["abc", "def", "g", "hi"]);
+    }
+}
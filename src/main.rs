@@ -1,59 +1,230 @@
 use anyhow::Context;
 use futures::StreamExt;
 use reqwest::Client as ReqwestClient;
-use std::{env, future::Future, net::ToSocketAddrs, sync::Arc};
-use tracing::{debug, info, trace, warn};
+use std::{
+    env,
+    future::Future,
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{signal, task::JoinHandle};
+use tracing::{debug, info, info_span, trace, warn, Instrument};
+use tracing_subscriber::EnvFilter;
 use twilight_cache_inmemory::InMemoryCache;
-use twilight_gateway::{Event, Intents, Shard};
+use twilight_gateway::{shard::Events, Event, Intents, Shard};
 use twilight_http::Client as HttpClient;
 use twilight_lavalink::{http::Track, model::IncomingEvent, Lavalink};
-use twilight_model::channel::Message;
+use twilight_model::{
+    channel::{embed::Embed, message::AllowedMentions, Message},
+    gateway::payload::incoming::MessageCreate,
+    id::{ChannelId, GuildId, UserId},
+};
 use twilight_standby::Standby;
 
 mod action;
+mod auto_leave;
+mod commands;
+mod cooldowns;
+mod embeds;
+mod health;
 mod helper;
+mod interactions;
+mod lyrics;
+mod metrics;
+mod parser;
 mod per_guild_data;
+mod permissions;
+mod persistence;
 mod player;
+mod playlists;
+mod resolve;
 mod response_context;
 mod state;
+mod track_cache;
 mod voice_channel;
 
+use cooldowns::Cooldowns;
 use helper::user_voice_channel;
+use per_guild_data::Store;
 use response_context::ResponseContext;
-use state::State;
+use state::{LavalinkHost, State};
+use track_cache::TrackCache;
+
+const DEFAULT_LOG_FILTER: &str = "info,musicbot2=debug";
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    // Initialize the tracing subscriber.
-    tracing_subscriber::fmt::init();
+    // Initialize the tracing subscriber, filtered with `RUST_LOG` if it's
+    // set. The JSON lines carry the fields of the current span and its
+    // parents along with the event.
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+        _ => subscriber.init(),
+    }
 
     let (state, mut events) = {
         let token =
             env::var("DISCORD_TOKEN").with_context(|| "unable to obtain DISCORD_TOKEN env var")?;
         let command_prefix = env::var("PREFIX").unwrap_or_else(|_| "!".to_owned());
+        let reply_mention = env_parse("REPLY_MENTION")?.unwrap_or(false);
+        let empty_channel_timeout =
+            Duration::from_secs(env_parse("EMPTY_CHANNEL_TIMEOUT")?.unwrap_or(60));
+        let idle_timeout = Duration::from_secs(env_parse("IDLE_TIMEOUT")?.unwrap_or(5 * 60));
+        let idle_warning = Some(Duration::from_secs(
+            env_parse("IDLE_WARNING")?.unwrap_or(60),
+        ))
+        .filter(|warning| !warning.is_zero());
+        let stop_confirmation_threshold = env_parse("STOP_CONFIRMATION_THRESHOLD")?.unwrap_or(10);
+        let max_queue_len = env_parse("MAX_QUEUE_LEN")?.unwrap_or(player::DEFAULT_MAX_QUEUE_LEN);
+        let lavalink_hosts = env::var("LAVALINK_HOSTS")
+            .or_else(|_| env::var("LAVALINK_HOST"))
+            .with_context(|| "unable to obtain LAVALINK_HOSTS env var")?
+            .split(',')
+            .map(str::trim)
+            .map(|lavalink_host| {
+                let (name, host) = match lavalink_host.split_once('@') {
+                    Some((name, host)) => (Some(name.to_owned()), host),
+                    None => (None, lavalink_host),
+                };
+                let address = host
+                    .to_socket_addrs()
+                    .with_context(|| format!("unable to parse lavalink host {}", lavalink_host))?
+                    .next()
+                    .with_context(|| {
+                        format!("unable to resolve lavalink host {}", lavalink_host)
+                    })?;
+                Ok::<_, anyhow::Error>(LavalinkHost { name, address })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let lavalink_authorization = env::var("LAVALINK_AUTHORIZATION")
+            .with_context(|| "unable to obtain LAVALINK_AUTHORIZATION env var")?;
+        let owner_ids = env::var("OWNER_ID")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|owner_id| !owner_id.is_empty())
+            .map(|owner_id| {
+                owner_id
+                    .parse()
+                    .ok()
+                    .and_then(UserId::new)
+                    .with_context(|| format!("unable to parse owner id {}", owner_id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let search_platform = env_parse("SEARCH_PLATFORM")?.unwrap_or_default();
+        let queue_state_path = env::var_os("QUEUE_STATE_PATH").map(PathBuf::from);
+        let playlists = playlists::Playlists::load(
+            env::var_os("PLAYLISTS_PATH").map(PathBuf::from),
+            env_parse("MAX_PLAYLISTS")?.unwrap_or(playlists::DEFAULT_MAX_PLAYLISTS),
+        )
+        .with_context(|| "unable to load playlists")?;
+        let fade_duration = env_parse("FADE_DURATION_MS")?
+            .filter(|millis| *millis > 0)
+            .map(Duration::from_millis);
+        let progress_update_interval = Some(Duration::from_secs(
+            env_parse("PROGRESS_UPDATE_INTERVAL")?.unwrap_or(5),
+        ))
+        .filter(|interval| !interval.is_zero());
+        let spotify = match (
+            env::var("SPOTIFY_CLIENT_ID"),
+            env::var("SPOTIFY_CLIENT_SECRET"),
+        ) {
+            (Ok(client_id), Ok(client_secret)) => {
+                Some(resolve::Spotify::new(client_id, client_secret))
+            }
+            _ => None,
+        };
+        let cooldown_windows = cooldowns::parse_windows(
+            &env::var("COOLDOWNS").unwrap_or_else(|_| cooldowns::DEFAULT_COOLDOWNS.to_owned()),
+        )
+        .with_context(|| "unable to parse COOLDOWNS env var")?;
+        let lyrics = env::var("LYRICS_API_URL")
+            .ok()
+            .map(|base_url| lyrics::Lyrics::new(base_url, env::var("LYRICS_API_TOKEN").ok()));
+        let http_connect_timeout =
+            Duration::from_secs(env_parse("HTTP_CONNECT_TIMEOUT")?.unwrap_or(5));
+        let http_timeout = Duration::from_secs(env_parse("HTTP_TIMEOUT")?.unwrap_or(30));
+        let track_cache_size = env_parse("TRACK_CACHE_SIZE")?.unwrap_or(256);
+        let track_cache_ttl = Duration::from_secs(env_parse("TRACK_CACHE_TTL")?.unwrap_or(10 * 60));
         let shard_count = 1u64;
 
-        let http = HttpClient::new(token.clone());
+        // Never ping anyone unless a message explicitly allows it.
+        let http = HttpClient::builder()
+            .token(token.clone())
+            .default_allowed_mentions(AllowedMentions::default())
+            .build();
         let user_id = http.current_user().exec().await?.model().await?.id;
+        let application_id = http
+            .current_user_application()
+            .exec()
+            .await?
+            .model()
+            .await?
+            .id;
+        http.set_application_id(application_id);
 
         let lavalink = Lavalink::new(user_id, shard_count);
 
+        let reqwest = ReqwestClient::builder()
+            .connect_timeout(http_connect_timeout)
+            .timeout(http_timeout)
+            .build()?;
+
         let cache = InMemoryCache::new();
 
-        let (shard, events) =
-            Shard::new(token, Intents::GUILD_MESSAGES | Intents::GUILD_VOICE_STATES);
+        let (shard, events) = Shard::new(
+            token,
+            Intents::GUILDS
+                | Intents::GUILD_MESSAGES
+                | Intents::GUILD_MESSAGE_REACTIONS
+                | Intents::GUILD_VOICE_STATES,
+        );
         shard.start().await?;
 
         (
             State {
                 http,
                 lavalink,
-                reqwest: ReqwestClient::new(),
+                reqwest,
                 shard,
                 standby: Standby::new(),
                 cache,
-                command_prefix,
-                per_guild_data: Default::default(),
+                per_guild_data: Store::new(command_prefix, max_queue_len, search_platform),
+                reply_mention,
+                user_id,
+                empty_channel_timeout,
+                idle_timeout,
+                idle_warning,
+                queue_state_path,
+                metrics: Default::default(),
+                track_cache: TrackCache::new(track_cache_size, track_cache_ttl),
+                fade_duration,
+                node_stats: Default::default(),
+                progress_update_interval,
+                spotify,
+                lavalink_names: lavalink_hosts
+                    .iter()
+                    .filter_map(|host| Some((host.address, host.name.clone()?)))
+                    .collect(),
+                lavalink_hosts,
+                lavalink_authorization,
+                owner_ids,
+                cooldowns: Cooldowns::new(cooldown_windows),
+                lyrics,
+                queue_listings: Default::default(),
+                stop_confirmation_threshold,
+                guild_locks: Default::default(),
+                playlists,
             },
             events,
         )
@@ -61,64 +232,303 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let state = Arc::new(state);
 
-    {
-        let lavalink_host = env::var("LAVALINK_HOST")
-            .with_context(|| "unable to obtain LAVALINK_HOST env var")?
-            .to_socket_addrs()
-            .with_context(|| "unable to parse lavalink host")?
-            .next()
-            .with_context(|| "unable to resolve lavalink host")?;
-        let lavalink_auth = env::var("LAVALINK_AUTHORIZATION")
-            .with_context(|| "unable to obtain LAVALINK_AUTHORIZATION env var")?;
+    if let Err(err) = interactions::register(&state).await {
+        warn!(message = "unable to register slash commands", ?err);
+    }
+
+    if let Some(path) = state.queue_state_path.clone() {
+        let snapshot = persistence::load(&path)
+            .with_context(|| format!("unable to load queues from {}", path.display()))?;
+        if let Some(snapshot) = snapshot {
+            info!(message = "restoring queues", guilds = snapshot.len());
+            state.per_guild_data.restore(snapshot);
+        }
+        tokio::spawn(persistence::run(Arc::clone(&state), path));
+    }
 
-        let (_, mut lavalink_rx) = state.lavalink.add(lavalink_host, lavalink_auth).await?;
+    let state2 = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(COOLDOWNS_PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            state2.cooldowns.prune();
+        }
+    });
+
+    if let Some(metrics_addr) = env_parse::<SocketAddr>("METRICS_ADDR")? {
+        let state2 = Arc::clone(&state);
+        spawn(async move { metrics::serve(state2, metrics_addr).await });
+        info!(message = "serving metrics", %metrics_addr);
+    }
 
+    if let Some(health_addr) = env_parse::<SocketAddr>("HEALTH_ADDR")? {
         let state2 = Arc::clone(&state);
-        tokio::spawn(async move {
+        spawn(async move { health::serve(state2, health_addr).await });
+        info!(message = "serving health checks", %health_addr);
+    }
+
+    for lavalink_host in &state.lavalink_hosts {
+        connect_lavalink_node(&state, lavalink_host).await?;
+        info!(message = "added lavalink node", %lavalink_host);
+    }
+
+    let shard_reconnect_attempts: u32 = env_parse("SHARD_RECONNECT_ATTEMPTS")?.unwrap_or(5);
+
+    let shutdown_signal = shutdown_signal();
+    tokio::pin!(shutdown_signal);
+
+    let mut reconnect_attempt = 0;
+    loop {
+        info!(message = "processing events");
+
+        let processed = match process_events(&state, &mut events, &mut shutdown_signal).await {
+            EventLoopExit::Shutdown => break,
+            EventLoopExit::EventsEnded { processed } => processed,
+        };
+
+        // Start counting anew if the shard has been working since the last
+        // reconnect.
+        if processed > 0 {
+            reconnect_attempt = 0;
+        }
+        if reconnect_attempt >= shard_reconnect_attempts {
+            warn!(
+                message = "giving up reconnecting the shard",
+                reconnect_attempt
+            );
+            break;
+        }
+        reconnect_attempt += 1;
+
+        let backoff = SHARD_RECONNECT_BACKOFF * 2u32.pow(reconnect_attempt - 1);
+        warn!(
+            message = "shard event stream ended, reconnecting",
+            reconnect_attempt,
+            ?backoff
+        );
+        tokio::time::sleep(backoff).await;
+
+        if let Err(err) = state.shard.start().await {
+            warn!(message = "unable to restart the shard", ?err);
+        }
+    }
+
+    shutdown(&state).await;
+
+    Ok(())
+}
+
+/// Add the Lavalink node and start processing its events.
+async fn connect_lavalink_node(
+    state: &Arc<State>,
+    host: &LavalinkHost,
+) -> Result<(), anyhow::Error> {
+    let (_, mut lavalink_rx) = state
+        .lavalink
+        .add(host.address, state.lavalink_authorization.clone())
+        .await?;
+
+    let state2 = Arc::clone(state);
+    let host = host.clone();
+    let span = info_span!("lavalink_node", node = %host);
+    tokio::spawn(
+        async move {
             while let Some(event) = lavalink_rx.next().await {
-                process_lavalink_event(&state2, event);
+                process_lavalink_event(&state2, &host, event);
             }
-        });
-    }
+        }
+        .instrument(span),
+    );
+    Ok(())
+}
+
+/// Measure the round trip of an HTTP request to the least loaded Lavalink
+/// node.
+async fn lavalink_latency(state: &State) -> Result<Duration, anyhow::Error> {
+    let node = state.lavalink.best().await?;
+    let config = node.config();
+    let started = Instant::now();
+    state
+        .reqwest
+        .get(format!("http://{}/version", config.address))
+        .header("Authorization", &config.authorization)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(started.elapsed())
+}
+
+const SHARD_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const COOLDOWNS_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+enum EventLoopExit {
+    Shutdown,
+    EventsEnded { processed: u64 },
+}
 
-    info!(message = "processing events");
+async fn process_events<S>(
+    state: &Arc<State>,
+    events: &mut Events,
+    shutdown_signal: &mut S,
+) -> EventLoopExit
+where
+    S: Future<Output = Result<(), anyhow::Error>> + Unpin,
+{
+    let mut processed = 0;
+    loop {
+        let event = tokio::select! {
+            res = &mut *shutdown_signal => {
+                if let Err(err) = res {
+                    warn!(message = "unable to listen for shutdown signal", ?err);
+                }
+                info!(message = "got shutdown signal");
+                return EventLoopExit::Shutdown;
+            }
+            event = events.next() => match event {
+                Some(val) => val,
+                None => return EventLoopExit::EventsEnded { processed },
+            },
+        };
+        processed += 1;
 
-    while let Some(event) = events.next().await {
         trace!(message = "start event handling", ?event);
+        if let Event::ShardDisconnected(disconnected) = &event {
+            // The shard reconnects on its own after this.
+            warn!(message = "shard disconnected", ?disconnected);
+        }
         state.cache.update(&event);
         state.standby.process(&event);
         let event2 = event.clone();
-        let state2 = Arc::clone(&state);
+        let state2 = Arc::clone(state);
         spawn(async move {
             state2.lavalink.process(&event2).await?;
             Ok(())
         });
-        process_event(&state, &event);
+        process_event(state, &event);
         trace!(message = "finish event handling", ?event);
     }
+}
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[cfg(unix)]
+async fn shutdown_signal() -> Result<(), anyhow::Error> {
+    use signal::unix::{signal as unix_signal, SignalKind};
+
+    let mut sigterm = unix_signal(SignalKind::terminate())?;
+    tokio::select! {
+        res = signal::ctrl_c() => res?,
+        _ = sigterm.recv() => {}
+    }
+    Ok(())
+}
 
+#[cfg(not(unix))]
+async fn shutdown_signal() -> Result<(), anyhow::Error> {
+    signal::ctrl_c().await?;
     Ok(())
 }
 
+async fn shutdown(state: &State) {
+    info!(message = "disconnecting from voice channels");
+
+    let guild_ids = state.per_guild_data.guild_ids();
+    let stops = guild_ids
+        .into_iter()
+        .filter(|guild_id| state.lavalink.players().get(guild_id).is_some())
+        .map(|guild_id| async move {
+            if let Err(err) = action::disconnect(state, guild_id).await {
+                warn!(message = "unable to disconnect player", %guild_id, ?err);
+            }
+        });
+
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, futures::future::join_all(stops))
+        .await
+        .is_err()
+    {
+        warn!(message = "timed out disconnecting from voice channels");
+    }
+
+    if let Some(path) = &state.queue_state_path {
+        info!(message = "saving queues");
+        if let Err(err) = persistence::save(path, &state.per_guild_data.snapshot()).await {
+            warn!(message = "unable to save queues", ?err);
+        }
+    }
+
+    state.shard.shutdown();
+}
+
+fn env_parse<T>(name: &str) -> Result<Option<T>, anyhow::Error>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    env::var(name)
+        .ok()
+        .map(|val| val.parse())
+        .transpose()
+        .with_context(|| format!("unable to parse {} env var", name))
+}
+
+/// Like `spawn`, but tells the user when the command fails instead of
+/// failing silently; the details go to the log.
+fn spawn_command<F>(response_context: ResponseContext, fut: F)
+where
+    F: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+{
+    spawn(async move {
+        match fut.await {
+            Err(err) => {
+                let content = response_context::error_content(&err).unwrap_or_else(|| {
+                    warn!("handler error: {:?}", err);
+                    format!("Something went wrong: {}", err)
+                });
+                response_context.with_content(&content).await?;
+                Ok(())
+            }
+            Ok(()) => Ok(()),
+        }
+    })
+}
+
 fn spawn<F>(fut: F)
 where
     F: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
 {
-    tokio::spawn(async move {
-        if let Err(why) = fut.await {
-            warn!("handler error: {:?}", why);
+    spawn_with_handle(fut);
+}
+
+fn spawn_with_handle<F>(fut: F) -> JoinHandle<()>
+where
+    F: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+{
+    tokio::spawn(
+        async move {
+            if let Err(why) = fut.await {
+                warn!("handler error: {:?}", why);
+            }
         }
-    });
+        .in_current_span(),
+    )
 }
 
 fn process_event(state: &Arc<State>, event: &Event) {
-    let msg = match event {
-        Event::MessageCreate(msg) => msg,
-        _ => return,
-    };
-
-    let msg: &Message = &msg.0;
+    match event {
+        Event::MessageCreate(msg) => process_message(state, &msg.0),
+        Event::InteractionCreate(interaction) => {
+            interactions::process_interaction(state, &interaction.0)
+        }
+        Event::VoiceStateUpdate(update) => {
+            voice_channel::process_voice_state_update(state, &update.0);
+            voice_channel::follow_controller(state, &update.0);
+            auto_leave::process_voice_state_update(state, &update.0);
+        }
+        _ => {}
+    }
+}
 
+fn process_message(state: &Arc<State>, msg: &Message) {
     let guild_id = match msg.guild_id {
         Some(val) => val,
         None => {
@@ -127,7 +537,8 @@ fn process_event(state: &Arc<State>, event: &Event) {
         }
     };
 
-    let command = match msg.content.strip_prefix(&state.command_prefix) {
+    let command_prefix = state.per_guild_data.get_prefix(guild_id);
+    let command = match msg.content.strip_prefix(command_prefix.as_str()) {
         Some(val) => val,
         None => {
             debug!(message = "skipping non-command message", ?msg);
@@ -135,37 +546,108 @@ fn process_event(state: &Arc<State>, event: &Event) {
         }
     };
 
-    let args: Vec<String> = command.split(' ').map(ToOwned::to_owned).collect();
+    let args: Vec<String> = parser::tokenize(command);
     let mut args = args.into_iter();
 
     let command = match args.next() {
-        Some(val) => val,
+        Some(val) => commands::resolve_alias(&val).to_owned(),
         None => {
             debug!(message = "skipping message without a command", ?msg);
             return;
         }
     };
-    info!(message = "got command", %command, args = ?args.as_slice());
+    // Correlate everything the command does, including the spawned tasks.
+    let span = info_span!("command", %guild_id, user_id = %msg.author.id, %command);
+    let _enter = span.enter();
+    info!(message = "got command", args = ?args.as_slice());
+    state.metrics.command_processed();
 
-    let response_context = ResponseContext::new(Arc::clone(state), &msg);
+    let response_context = ResponseContext::new(
+        Arc::clone(state),
+        &msg,
+        state.reply_mention,
+        command_prefix.clone(),
+    );
     state
         .per_guild_data
         .associate_text_channel(guild_id, msg.channel_id);
 
+    // Querying the volume is fine for everyone.
+    let is_volume_query = command == "volume" && args.as_slice().is_empty();
+    if commands::DJ_COMMANDS.contains(&command.as_str())
+        && !is_volume_query
+        && !permissions::is_dj(state, guild_id, msg.author.id)
+    {
+        spawn(async move {
+            response_context
+                .with_content("You need the DJ role to do that")
+                .await?;
+            Ok(())
+        });
+        return;
+    }
+
+    if commands::SAME_CHANNEL_COMMANDS.contains(&command.as_str())
+        && !permissions::in_bot_voice_channel(state, guild_id, msg.author.id)
+    {
+        spawn(async move {
+            response_context
+                .with_content("You must be in my voice channel to do that")
+                .await?;
+            Ok(())
+        });
+        return;
+    }
+
+    if commands::OWNER_COMMANDS.contains(&command.as_str())
+        && !permissions::is_owner(state, msg.author.id)
+    {
+        spawn(async move {
+            response_context
+                .with_content("This command is owner-only")
+                .await?;
+            Ok(())
+        });
+        return;
+    }
+
+    if let Some(remaining) = state.cooldowns.check(guild_id, msg.author.id, &command) {
+        spawn(async move {
+            response_context
+                .with_content(&format!(
+                    "Slow down, try again in {}s",
+                    remaining.as_secs_f64().ceil()
+                ))
+                .await?;
+            Ok(())
+        });
+        return;
+    }
+
     let state = Arc::clone(state);
     match command.as_ref() {
-        "play" => {
+        "join" => {
             let author_id = msg.author.id;
-            spawn(async move {
-                let identifier = match args.next() {
+            spawn_command(response_context.clone(), async move {
+                let channel_id = match user_voice_channel(&state, guild_id, author_id).await? {
                     Some(val) => val,
                     None => {
                         response_context
-                            .with_content("Pass track as an argument")
+                            .with_content("You need to join a voice channel first")
                             .await?;
                         return Ok(());
                     }
                 };
+                action::join(&state, guild_id, channel_id).await?;
+                response_context
+                    .with_content(&format!("Joined <#{}>", channel_id))
+                    .await?;
+                Ok(())
+            })
+        }
+        "summon" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
                 let channel_id = match user_voice_channel(&state, guild_id, author_id).await? {
                     Some(val) => val,
                     None => {
@@ -175,29 +657,172 @@ fn process_event(state: &Arc<State>, event: &Event) {
                         return Ok(());
                     }
                 };
-                match action::play(&state, guild_id, channel_id, identifier).await {
-                    Ok(track) => {
+                match action::move_to(&state, guild_id, channel_id).await {
+                    Ok(()) => {
+                        response_context
+                            .with_content(&format!("Moved to <#{}>", channel_id))
+                            .await?;
+                        Ok(())
+                    }
+                    Err(err) if err.is::<action::NotConnected>() => {
                         response_context
-                            .with_content(&format!("Playing {}", format_track(&track)))
+                            .with_content(&format!(
+                                "I'm not in a voice channel, use `{}join` instead",
+                                response_context.prefix()
+                            ))
                             .await?;
                         Ok(())
                     }
-                    Err(err) if err.is::<action::NoTracksFound>() => {
+                    Err(err) => Err(err)?,
+                }
+            })
+        }
+        "play" | "playnow" => {
+            let author_id = msg.author.id;
+            let replace = command == "playnow";
+            spawn_command(response_context.clone(), async move {
+                let _guard = state.lock_guild(guild_id).await;
+                let (args, range) = parser::play_range(args.collect());
+                let identifier = match parser::track_identifier(
+                    args,
+                    state.per_guild_data.get_search_platform(guild_id),
+                ) {
+                    Some(val) => val,
+                    None => {
+                        response_context
+                            .with_usage("Pass track as an argument", "play")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                let channel_id = match user_voice_channel(&state, guild_id, author_id).await? {
+                    Some(val) => val,
+                    None => {
+                        response_context
+                            .with_content("You need to join a voice channel first")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                let mut identifiers = resolve::resolve(&state, identifier).await?.into_iter();
+                let identifier = match identifiers.next() {
+                    Some(val) => val,
+                    None => {
                         response_context.with_content("No tracks found").await?;
+                        return Ok(());
+                    }
+                };
+                match action::play(
+                    &state, guild_id, channel_id, identifier, author_id, range, replace,
+                )
+                .await
+                {
+                    Ok(played) => {
+                        let embed = match played {
+                            action::Played::Playing(track) => {
+                                embeds::track(&format_play_heading(range), &track)
+                            }
+                            action::Played::Enqueued(track) => embeds::track("Enqueued", &track),
+                        };
+                        response_context.with_embed(embed).await?;
+
+                        // Queue the rest of the album or the playlist.
+                        let enqueued =
+                            enqueue_resolved(&state, guild_id, channel_id, identifiers, author_id)
+                                .await;
+                        if enqueued > 0 {
+                            response_context
+                                .with_content(&format!("Enqueued {} more tracks", enqueued))
+                                .await?;
+                        }
                         Ok(())
                     }
                     Err(err) => Err(err)?,
                 }
             })
         }
-        "add" | "enqueue" => {
+        "search" => {
             let author_id = msg.author.id;
-            spawn(async move {
-                let identifier = match args.next() {
+            let text_channel_id = msg.channel_id;
+            spawn_command(response_context.clone(), async move {
+                let query = args.collect::<Vec<_>>().join(" ");
+                if query.is_empty() {
+                    response_context
+                        .with_usage("Pass search query as an argument", "search")
+                        .await?;
+                    return Ok(());
+                }
+                let channel_id = match user_voice_channel(&state, guild_id, author_id).await? {
+                    Some(val) => val,
+                    None => {
+                        response_context
+                            .with_content("You need to join a voice channel first")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                let tracks = action::search(&state, guild_id, query).await?;
+                if tracks.is_empty() {
+                    response_context.with_content("No tracks found").await?;
+                    return Ok(());
+                }
+
+                let listing: Vec<String> = tracks
+                    .iter()
+                    .enumerate()
+                    .map(|(index, track)| format!("{}. {}", index + 1, format_track(track)))
+                    .collect();
+                // Ping the user as the bot is waiting for them now.
+                let prompt = format!(
+                    "{}\nReply with the number of the track to play",
+                    listing.join("\n")
+                );
+                response_context
+                    .with_reply_mention(|msg| Ok(msg.content(&prompt)?))
+                    .await?;
+
+                let count = tracks.len();
+                let selection = state.standby.wait_for_message(
+                    text_channel_id,
+                    move |event: &MessageCreate| {
+                        event.author.id == author_id
+                            && matches!(
+                                event.content.trim().parse::<usize>(),
+                                Ok(val) if (1..=count).contains(&val)
+                            )
+                    },
+                );
+                let selection = match tokio::time::timeout(SEARCH_TIMEOUT, selection).await {
+                    Ok(Ok(val)) => val,
+                    _ => {
+                        response_context.with_content("Search timed out").await?;
+                        return Ok(());
+                    }
+                };
+                let index: usize = selection.content.trim().parse()?;
+                let track = tracks.into_iter().nth(index - 1).unwrap();
+
+                let _guard = state.lock_guild(guild_id).await;
+                let track =
+                    action::play_track(&state, guild_id, channel_id, track, author_id).await?;
+                response_context
+                    .with_embed(embeds::track("Playing", &track))
+                    .await?;
+                Ok(())
+            })
+        }
+        "add" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                let _guard = state.lock_guild(guild_id).await;
+                let identifier = match parser::track_identifier(
+                    args,
+                    state.per_guild_data.get_search_platform(guild_id),
+                ) {
                     Some(val) => val,
                     None => {
                         response_context
-                            .with_content("Pass track as an argument")
+                            .with_usage("Pass track as an argument", "add")
                             .await?;
                         return Ok(());
                     }
@@ -211,113 +836,1366 @@ fn process_event(state: &Arc<State>, event: &Event) {
                         return Ok(());
                     }
                 };
-                match action::enqueue(&state, guild_id, channel_id, identifier).await {
-                    Ok(track) => {
+                let identifiers = resolve::resolve(&state, identifier).await?;
+                let identifier = match identifiers.as_slice() {
+                    [] => {
+                        response_context.with_content("No tracks found").await?;
+                        return Ok(());
+                    }
+                    [identifier] => identifier.clone(),
+                    _ => {
+                        let enqueued =
+                            enqueue_resolved(&state, guild_id, channel_id, identifiers, author_id)
+                                .await;
+                        response_context
+                            .with_content(&format!("Enqueued {} tracks", enqueued))
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                match action::enqueue(&state, guild_id, channel_id, identifier, author_id).await {
+                    Ok(action::Enqueued::Track(track)) => {
                         response_context
-                            .with_content(&format!("Enqueued {}", format_track(&track)))
+                            .with_embed(embeds::track("Enqueued", &track))
                             .await?;
                         Ok(())
                     }
-                    Err(err) if err.is::<action::NoTracksFound>() => {
-                        response_context.with_content("No tracks found").await?;
+                    Ok(action::Enqueued::Playlist {
+                        name,
+                        count,
+                        skipped,
+                    }) => {
+                        let mut content = format!(
+                            "Enqueued {} tracks from playlist {}",
+                            count,
+                            name.as_deref().unwrap_or("")
+                        );
+                        if skipped > 0 {
+                            content.push_str(&format!(
+                                ", skipped {} tracks as the queue is full",
+                                skipped
+                            ));
+                        }
+                        response_context.with_content(&content).await?;
                         Ok(())
                     }
                     Err(err) => Err(err)?,
                 }
             })
         }
-        "stop" => spawn(async move { action::stop(&state, guild_id).await }),
-        "volume" => spawn(async move {
-            let value = match args.next() {
-                Some(val) => val,
-                None => {
+        "addmany" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                let _guard = state.lock_guild(guild_id).await;
+                let identifiers: Vec<String> = args.collect();
+                if identifiers.is_empty() {
                     response_context
-                        .with_content("Pass volume value as an argument")
+                        .with_usage("Pass tracks as arguments", "addmany")
                         .await?;
                     return Ok(());
                 }
-            };
-            let value = match value.parse() {
-                Ok(value) => value,
-                Err(err) => {
-                    response_context
-                        .with_content(&format!("Volume value is invalid: {}", err))
-                        .await?;
+                let channel_id = match user_voice_channel(&state, guild_id, author_id).await? {
+                    Some(val) => val,
+                    None => {
+                        response_context
+                            .with_content("You need to join a voice channel first")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                match action::enqueue_many(&state, guild_id, channel_id, identifiers, author_id)
+                    .await
+                {
+                    Ok((added, failed, skipped)) => {
+                        let mut content = format!("Added {} tracks, {} failed", added, failed);
+                        if skipped > 0 {
+                            content.push_str(&format!(", {} skipped, queue is full", skipped));
+                        }
+                        response_context.with_content(&content).await?;
+                        Ok(())
+                    }
+                    Err(err) => Err(err)?,
+                }
+            })
+        }
+        "stop" => {
+            let author_id = msg.author.id;
+            let text_channel_id = msg.channel_id;
+            spawn_command(response_context.clone(), async move {
+                if !helper::confirm_stop(&state, guild_id, text_channel_id, author_id).await? {
+                    response_context.with_content("Stop cancelled").await?;
                     return Ok(());
                 }
-            };
-            match action::volume(&state, guild_id, value).await {
-                Ok(val) => {
+
+                let _guard = state.lock_guild(guild_id).await;
+                action::stop(&state, guild_id).await
+            })
+        }
+        "leave" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            action::disconnect(&state, guild_id).await?;
+            response_context
+                .with_content("Disconnected, the queue is kept")
+                .await?;
+            Ok(())
+        }),
+        "skip" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            match action::skip(&state, guild_id).await? {
+                Some(track) => {
                     response_context
-                        .with_content(&format!("Volume was set to {}", val))
+                        .with_content(&format!("Skipped, playing {}", format_track(&track.track)))
                         .await?;
-                    Ok(())
                 }
-                Err(err) if err.is::<action::VolumeValueOutOfBounds>() => {
+                None => {
                     response_context
-                        .with_content(&format!("Invalid volume value: {}", err))
+                        .with_content("Nothing left to play")
                         .await?;
-                    Ok(())
                 }
-                Err(err) => Err(err)?,
             }
+            Ok(())
         }),
-        "seek" => spawn(async move {
-            let value = match args.next() {
-                Some(val) => val,
-                None => {
-                    response_context
-                        .with_content("Pass seek position in milliseconds as an argument")
+        "previous" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                let _guard = state.lock_guild(guild_id).await;
+                match action::previous(&state, guild_id, author_id).await {
+                    Ok(Some(track)) => {
+                        response_context
+                            .with_content(&format!("Replaying {}", format_track(&track.track)))
+                            .await?;
+                        Ok(())
+                    }
+                    Ok(None) => {
+                        response_context.with_content("No previous track").await?;
+                        Ok(())
+                    }
+                    Err(err) => Err(err)?,
+                }
+            })
+        }
+        "forceskip" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            match action::force_skip(&state, guild_id).await? {
+                Some(track) => {
+                    response_context
+                        .with_content(&format!("Skipped, playing {}", format_track(&track.track)))
+                        .await?;
+                }
+                None => {
+                    response_context.with_content("Queue empty").await?;
+                }
+            }
+            Ok(())
+        }),
+        "jump" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            let value = match args.next() {
+                Some(val) => val,
+                None => {
+                    response_context
+                        .with_usage("Pass queue position as an argument", "jump")
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let index: usize = match value.parse() {
+                Ok(val) => val,
+                Err(err) => {
+                    response_context
+                        .with_content(&format!("Queue position is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            match action::jump(&state, guild_id, index).await {
+                Ok(track) => {
+                    response_context
+                        .with_content(&format!("Jumped to {}", format_track(&track.track)))
+                        .await?;
+                    Ok(())
+                }
+                Err(err) if err.is::<player::QueueIndexError>() => {
+                    response_context
+                        .with_content(&format!("No track at position {}", index))
+                        .await?;
+                    Ok(())
+                }
+                Err(err) => Err(err)?,
+            }
+        }),
+        "skipto" => spawn_command(response_context.clone(), async move {
+            let query = args.collect::<Vec<_>>().join(" ");
+            if query.is_empty() {
+                response_context
+                    .with_usage("Pass part of the track title as an argument", "skipto")
+                    .await?;
+                return Ok(());
+            }
+            let _guard = state.lock_guild(guild_id).await;
+            match action::skip_to(&state, guild_id, &query).await? {
+                Some(track) => {
+                    response_context
+                        .with_content(&format!("Skipped to {}", format_track(&track.track)))
+                        .await?;
+                }
+                None => {
+                    response_context
+                        .with_content("No matching track in queue")
+                        .await?;
+                }
+            }
+            Ok(())
+        }),
+        "queue" => spawn_command(response_context.clone(), async move {
+            let (pages, content) =
+                state
+                    .per_guild_data
+                    .read_track_manager(guild_id, |track_manager| {
+                        let tracks = track_manager.list();
+                        (queue_page_count(tracks.len()), format_queue(tracks, 0))
+                    });
+            if pages == 1 {
+                response_context.with_content(&content).await?;
+                return Ok(());
+            }
+
+            let components = interactions::queue_buttons();
+            let message = response_context
+                .with(|msg| Ok(msg.content(&content)?.components(&components)?))
+                .await?
+                .model()
+                .await?;
+            interactions::track_queue_listing(&state, guild_id, message.channel_id, message.id);
+            Ok(())
+        }),
+        "save" => spawn_command(response_context.clone(), async move {
+            let name = args.collect::<Vec<_>>().join(" ");
+            if name.is_empty() {
+                response_context
+                    .with_usage("Pass playlist name as an argument", "save")
+                    .await?;
+                return Ok(());
+            }
+            let tracks: Vec<Track> = state
+                .per_guild_data
+                .current_track(guild_id)
+                .into_iter()
+                .chain(
+                    state
+                        .per_guild_data
+                        .read_track_manager(guild_id, |track_manager| {
+                            track_manager.list().cloned().collect::<Vec<_>>()
+                        }),
+                )
+                .map(|queued| queued.track)
+                .collect();
+            if tracks.is_empty() {
+                response_context.with_content("Nothing to save").await?;
+                return Ok(());
+            }
+            let count = tracks.len();
+            match state.playlists.save(guild_id, name.clone(), tracks).await {
+                Ok(()) => {
+                    response_context
+                        .with_content(&format!("Saved {} tracks as {}", count, name))
+                        .await?;
+                    Ok(())
+                }
+                Err(err) => Err(err)?,
+            }
+        }),
+        "load" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                let name = args.collect::<Vec<_>>().join(" ");
+                if name.is_empty() {
+                    response_context
+                        .with_usage("Pass playlist name as an argument", "load")
+                        .await?;
+                    return Ok(());
+                }
+                let tracks = match state.playlists.get(guild_id, &name) {
+                    Some(val) => val,
+                    None => {
+                        response_context
+                            .with_content(&format!("No playlist named {}", name))
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                let channel_id = match user_voice_channel(&state, guild_id, author_id).await? {
+                    Some(val) => val,
+                    None => {
+                        response_context
+                            .with_content("You need to join a voice channel first")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                let _guard = state.lock_guild(guild_id).await;
+                match action::enqueue_tracks(&state, guild_id, channel_id, tracks, author_id).await
+                {
+                    Ok(count) => {
+                        response_context
+                            .with_content(&format!("Loaded {} tracks from {}", count, name))
+                            .await?;
+                        Ok(())
+                    }
+                    Err(err) => Err(err)?,
+                }
+            })
+        }
+        "playlists" => spawn_command(response_context.clone(), async move {
+            let playlists = state.playlists.list(guild_id);
+            let content = if playlists.is_empty() {
+                "No playlists saved yet".to_owned()
+            } else {
+                playlists
+                    .iter()
+                    .map(|(name, count)| format!("{} ({} tracks)", name, count))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            response_context.with_content(&content).await?;
+            Ok(())
+        }),
+        "deleteplaylist" => spawn_command(response_context.clone(), async move {
+            let name = args.collect::<Vec<_>>().join(" ");
+            if name.is_empty() {
+                response_context
+                    .with_usage("Pass playlist name as an argument", "deleteplaylist")
+                    .await?;
+                return Ok(());
+            }
+            let content = if state.playlists.delete(guild_id, &name).await? {
+                format!("Deleted playlist {}", name)
+            } else {
+                format!("No playlist named {}", name)
+            };
+            response_context.with_content(&content).await?;
+            Ok(())
+        }),
+        "history" => spawn_command(response_context.clone(), async move {
+            let history = state.per_guild_data.history(guild_id);
+            let content = if history.is_empty() {
+                "Nothing was played yet".to_owned()
+            } else {
+                history
+                    .iter()
+                    .take(QUEUE_PAGE_LEN)
+                    .enumerate()
+                    .map(|(index, track)| format!("{}. {}", index + 1, format_track(track)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            response_context.with_content(&content).await?;
+            Ok(())
+        }),
+        "replay" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                let _guard = state.lock_guild(guild_id).await;
+                let track = match state.per_guild_data.last_played(guild_id) {
+                    Some(val) => val,
+                    None => {
+                        response_context.with_content("Nothing to replay").await?;
+                        return Ok(());
+                    }
+                };
+                state
+                    .per_guild_data
+                    .with_track_manger(guild_id, |track_manager| {
+                        track_manager.enqueue_front(player::QueuedTrack {
+                            track: track.clone(),
+                            requested_by: author_id,
+                        })
+                    });
+                response_context
+                    .with_content(&format!("Queued {} to play next", format_track(&track)))
+                    .await?;
+                Ok(())
+            })
+        }
+        "dedupe" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            let removed = state
+                .per_guild_data
+                .with_track_manger(guild_id, |track_manager| track_manager.dedup());
+            response_context
+                .with_content(&format!("Removed {} duplicate tracks from queue", removed))
+                .await?;
+            Ok(())
+        }),
+        "clear" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            let removed = state
+                .per_guild_data
+                .with_track_manger(guild_id, |track_manager| track_manager.clear());
+            response_context
+                .with_content(&format!("Removed {} tracks from queue", removed))
+                .await?;
+            Ok(())
+        }),
+        "remove" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            let value = match args.next() {
+                Some(val) => val,
+                None => {
+                    response_context
+                        .with_usage("Pass queue position as an argument", "remove")
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let index: usize = match value.parse() {
+                Ok(val) => val,
+                Err(err) => {
+                    response_context
+                        .with_content(&format!("Queue position is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let removed = state
+                .per_guild_data
+                .with_track_manger(guild_id, |track_manager| track_manager.remove(index));
+            match removed {
+                Some(track) => {
+                    response_context
+                        .with_content(&format!("Removed {}", format_track(&track.track)))
+                        .await?;
+                }
+                None => {
+                    response_context
+                        .with_content(&format!("No track at position {}", index))
+                        .await?;
+                }
+            }
+            Ok(())
+        }),
+        "move" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            let (from, to) = match (args.next(), args.next()) {
+                (Some(from), Some(to)) => (from, to),
+                _ => {
+                    response_context
+                        .with_usage(
+                            "Pass source and destination queue positions as arguments",
+                            "move",
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let (from, to): (usize, usize) = match (from.parse(), to.parse()) {
+                (Ok(from), Ok(to)) => (from, to),
+                (Err(err), _) | (_, Err(err)) => {
+                    response_context
+                        .with_content(&format!("Queue position is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let moved = state.per_guild_data.with_track_manger(
+                guild_id,
+                |track_manager| -> Result<_, player::QueueIndexError> {
+                    track_manager.move_track(from, to)?;
+                    Ok(track_manager.list().nth(to - 1).cloned())
+                },
+            );
+            match moved {
+                Ok(track) => {
+                    let track = track
+                        .map(|track| format_track(&track.track))
+                        .unwrap_or_default();
+                    response_context
+                        .with_content(&format!("Moved {} to position {}", track, to))
+                        .await?;
+                }
+                Err(player::QueueIndexError { index }) => {
+                    response_context
+                        .with_content(&format!("No track at position {}", index))
+                        .await?;
+                }
+            }
+            Ok(())
+        }),
+        "bump" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            let index: usize = match args.next().map(|val| val.parse()) {
+                Some(Ok(val)) => val,
+                Some(Err(err)) => {
+                    response_context
+                        .with_content(&format!("Queue position is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+                None => {
+                    response_context
+                        .with_usage("Pass queue position as an argument", "bump")
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let bumped = state
+                .per_guild_data
+                .with_track_manger(guild_id, |track_manager| track_manager.bump(index));
+            match bumped {
+                Some(track) => {
+                    response_context
+                        .with_content(&format!(
+                            "Moved {} to the top of the queue",
+                            format_track(&track.track)
+                        ))
+                        .await?;
+                }
+                None => {
+                    response_context
+                        .with_content(&format!("No track at position {}", index))
+                        .await?;
+                }
+            }
+            Ok(())
+        }),
+        "swap" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            let (a, b) = match (args.next(), args.next()) {
+                (Some(a), Some(b)) => (a, b),
+                _ => {
+                    response_context
+                        .with_usage("Pass two queue positions as arguments", "swap")
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let (a, b): (usize, usize) = match (a.parse(), b.parse()) {
+                (Ok(a), Ok(b)) => (a, b),
+                (Err(err), _) | (_, Err(err)) => {
+                    response_context
+                        .with_content(&format!("Queue position is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let swapped = state.per_guild_data.with_track_manger(
+                guild_id,
+                |track_manager| -> Result<_, player::QueueIndexError> {
+                    track_manager.swap(a, b)?;
+                    // The tracks have switched places by now.
+                    let first = track_manager.list().nth(b - 1).cloned();
+                    let second = track_manager.list().nth(a - 1).cloned();
+                    Ok((first, second))
+                },
+            );
+            match swapped {
+                Ok((first, second)) => {
+                    let first = first
+                        .map(|track| format_track(&track.track))
+                        .unwrap_or_default();
+                    let second = second
+                        .map(|track| format_track(&track.track))
+                        .unwrap_or_default();
+                    response_context
+                        .with_content(&format!("Swapped {} and {}", first, second))
+                        .await?;
+                }
+                Err(player::QueueIndexError { index }) => {
+                    response_context
+                        .with_content(&format!("No track at position {}", index))
+                        .await?;
+                }
+            }
+            Ok(())
+        }),
+        "nowplaying" => spawn_command(response_context.clone(), async move {
+            let content = match action::now_playing(&state, guild_id).await? {
+                Some(now_playing) => format!(
+                    "Playing {} [{} / {}] on node {}",
+                    format_queued_track(&now_playing.track),
+                    format_duration(now_playing.position),
+                    format_duration(now_playing.track.track.info.length as i64),
+                    now_playing.node,
+                ),
+                None => "Nothing is playing".to_owned(),
+            };
+            response_context.with_content(&content).await?;
+            Ok(())
+        }),
+        "loop" => spawn_command(response_context.clone(), async move {
+            let value = match args.next() {
+                Some(val) => val,
+                None => {
+                    response_context
+                        .with_usage(
+                            "Pass loop mode (off, track or queue) as an argument",
+                            "loop",
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let loop_mode: player::LoopMode = match value.parse() {
+                Ok(val) => val,
+                Err(err) => {
+                    response_context
+                        .with_content(&format!("Loop mode is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            state
+                .per_guild_data
+                .with_track_manger(guild_id, |track_manager| {
+                    track_manager.set_loop_mode(loop_mode)
+                });
+            response_context
+                .with_content(&format!("Loop mode was set to {}", loop_mode))
+                .await?;
+            Ok(())
+        }),
+        "shuffle" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            let shuffled = state
+                .per_guild_data
+                .with_track_manger(guild_id, |track_manager| {
+                    if track_manager.list().len() < 2 {
+                        return None;
+                    }
+                    Some(track_manager.shuffle())
+                });
+            match shuffled {
+                Some(count) => {
+                    response_context
+                        .with_content(&format!("Shuffled {} tracks", count))
+                        .await?;
+                }
+                None => {
+                    response_context.with_content("Nothing to shuffle").await?;
+                }
+            }
+            Ok(())
+        }),
+        "volume" => spawn_command(response_context.clone(), async move {
+            let value = match args.next() {
+                Some(val) => val,
+                None => {
+                    let volume = state.per_guild_data.get_volume(guild_id);
+                    response_context
+                        .with_content(&format!("Current volume: {}", volume))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            // A leading sign adjusts the current volume.
+            let relative = value.starts_with('+') || value.starts_with('-');
+            let value = match value.parse() {
+                Ok(value) => value,
+                Err(err) => {
+                    response_context
+                        .with_content(&format!("Volume value is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let res = if relative {
+                action::volume_relative(&state, guild_id, value).await
+            } else {
+                action::volume(&state, guild_id, value).await
+            };
+            match res {
+                Ok(val) => {
+                    response_context
+                        .with_content(&format!("Volume was set to {}", val))
+                        .await?;
+                    Ok(())
+                }
+                Err(err) => Err(err)?,
+            }
+        }),
+        "bassboost" => spawn_command(response_context.clone(), async move {
+            let value = match args.next() {
+                Some(val) => val,
+                None => {
+                    response_context
+                        .with_usage(
+                            "Pass bass boost preset (off, low, medium or high) as an argument",
+                            "bassboost",
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let preset: player::BassPreset = match value.parse() {
+                Ok(val) => val,
+                Err(err) => {
+                    response_context
+                        .with_content(&format!("Bass boost preset is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let preset = action::equalizer(&state, guild_id, preset).await?;
+            response_context
+                .with_content(&format!("Bass boost was set to {}", preset))
+                .await?;
+            Ok(())
+        }),
+        "filters" => spawn_command(response_context.clone(), async move {
+            let value = match args.next() {
+                Some(val) => val,
+                None => {
+                    response_context
+                        .with_content(
+                            "Pass filters preset (nightcore, vaporwave or off) as an argument",
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let preset: player::FiltersPreset = match value.parse() {
+                Ok(val) => val,
+                Err(err) => {
+                    response_context
+                        .with_content(&format!("Filters preset is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let preset = action::filters(&state, guild_id, preset).await?;
+            response_context
+                .with_content(&format!("Filters were set to {}", preset))
+                .await?;
+            Ok(())
+        }),
+        "forward" | "rewind" => {
+            let direction = if command == "forward" { 1 } else { -1 };
+            spawn_command(response_context.clone(), async move {
+                let value = match args.next() {
+                    Some(val) => val,
+                    None => {
+                        response_context
+                            .with_usage(
+                                "Pass number of seconds as an argument",
+                                if direction > 0 { "forward" } else { "rewind" },
+                            )
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                let seconds: i64 = match value.parse() {
+                    Ok(val) => val,
+                    Err(err) => {
+                        response_context
+                            .with_content(&format!("Number of seconds is invalid: {}", err))
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                let delta_millis = direction * seconds.saturating_mul(1000);
+                match action::seek_relative(&state, guild_id, delta_millis).await {
+                    Ok(val) => {
+                        response_context
+                            .with_content(&format!("Position was set to {}", format_duration(val)))
+                            .await?;
+                        Ok(())
+                    }
+                    Err(err) => Err(err)?,
+                }
+            })
+        }
+        "restart" => spawn_command(response_context.clone(), async move {
+            if state.per_guild_data.current_track(guild_id).is_none() {
+                response_context.with_content("Nothing is playing").await?;
+                return Ok(());
+            }
+            action::seek(&state, guild_id, 0).await?;
+            response_context
+                .with_content("Restarted current track")
+                .await?;
+            Ok(())
+        }),
+        "seek" => {
+            spawn_command(response_context.clone(), async move {
+                let value = match args.next() {
+                    Some(val) => val,
+                    None => {
+                        response_context
+                        .with_usage("Pass seek position in milliseconds, mm:ss or hh:mm:ss as an argument", "seek")
+                        .await?;
+                        return Ok(());
+                    }
+                };
+                // Bare numbers are milliseconds, for compatibility.
+                let value = match value.parse().or_else(|_| parser::parse_timestamp(&value)) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        response_context
+                            .with_content(&format!("Position is invalid: {}", err))
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                match action::seek(&state, guild_id, value).await {
+                    Ok(val) => {
+                        response_context
+                            .with_content(&format!("Position was set to {}ms", val))
+                            .await?;
+                        Ok(())
+                    }
+                    Err(err) => Err(err)?,
+                }
+            })
+        }
+        "pause" | "resume" => {
+            let paused = command == "pause";
+            spawn_command(response_context.clone(), async move {
+                let _guard = state.lock_guild(guild_id).await;
+                match action::set_paused(&state, guild_id, paused).await {
+                    Ok(()) => {
+                        response_context
+                            .with_content(if paused { "Paused" } else { "Resumed" })
+                            .await?;
+                        Ok(())
+                    }
+                    Err(err) => Err(err)?,
+                }
+            })
+        }
+        "pausefor" => spawn_command(response_context.clone(), async move {
+            let value = match args.next() {
+                Some(val) => val,
+                None => {
+                    response_context
+                        .with_usage("Pass number of seconds as an argument", "pausefor")
+                        .await?;
+                    return Ok(());
+                }
+            };
+            let seconds: u64 = match value.parse() {
+                Ok(val) => val,
+                Err(err) => {
+                    response_context
+                        .with_content(&format!("Number of seconds is invalid: {}", err))
+                        .await?;
+                    return Ok(());
+                }
+            };
+
+            // Pausing again only moves the resume time.
+            let _guard = state.lock_guild(guild_id).await;
+            match action::set_paused(&state, guild_id, true).await {
+                Ok(()) => {}
+                Err(err) if err.is::<action::AlreadyPaused>() => {}
+                Err(err) => Err(err)?,
+            }
+
+            let state2 = Arc::clone(&state);
+            let timer = spawn_with_handle(async move {
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+                let _guard = state2.lock_guild(guild_id).await;
+
+                // Detach from the timer slot so that resuming doesn't abort us.
+                drop(state2.per_guild_data.take_resume_timer(guild_id));
+
+                match action::set_paused(&state2, guild_id, false).await {
+                    Ok(()) => {}
+                    Err(err) if err.is::<action::AlreadyPlaying>() => {}
+                    Err(err) => Err(err)?,
+                }
+                debug!(message = "resumed after pause", %guild_id);
+                Ok(())
+            });
+            state.per_guild_data.set_resume_timer(guild_id, Some(timer));
+
+            response_context
+                .with_content(&format!("Paused, resuming in {}s", seconds))
+                .await?;
+            Ok(())
+        }),
+        "pausetoggle" => spawn_command(response_context.clone(), async move {
+            let _guard = state.lock_guild(guild_id).await;
+            match action::pause_toggle(&state, guild_id).await {
+                Ok(val) => {
+                    response_context
+                        .with_content(if val { "Paused" } else { "Unpaused" })
+                        .await?;
+                    Ok(())
+                }
+                Err(err) => Err(err)?,
+            }
+        }),
+        "setdj" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                if !permissions::can_manage_channels(&state, guild_id, author_id) {
+                    response_context
+                        .with_content("You need the Manage Channels permission to do that")
+                        .await?;
+                    return Ok(());
+                }
+                let value = match args.next() {
+                    Some(val) => val,
+                    None => {
+                        response_context
+                            .with_usage("Pass DJ role or off as an argument", "setdj")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                if value == "off" {
+                    state.per_guild_data.set_dj_role(guild_id, None);
+                    response_context.with_content("DJ role was unset").await?;
+                    return Ok(());
+                }
+                let role_id = match parser::role_id(&value) {
+                    Some(val) => val,
+                    None => {
+                        response_context.with_content("Role is invalid").await?;
+                        return Ok(());
+                    }
+                };
+                state.per_guild_data.set_dj_role(guild_id, Some(role_id));
+                response_context
+                    .with_content(&format!("DJ role was set to <@&{}>", role_id))
+                    .await?;
+                Ok(())
+            })
+        }
+        "samechannel" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                if !permissions::can_manage_channels(&state, guild_id, author_id) {
+                    response_context
+                        .with_content("You need the Manage Channels permission to do that")
                         .await?;
                     return Ok(());
                 }
+                let required = match args.next().as_deref() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => {
+                        response_context
+                            .with_usage("Pass on or off as an argument", "samechannel")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                state
+                    .per_guild_data
+                    .set_same_channel_required(guild_id, required);
+                response_context
+                    .with_content(if required {
+                        "Controlling the playback now requires being in my voice channel"
+                    } else {
+                        "Controlling the playback no longer requires being in my voice channel"
+                    })
+                    .await?;
+                Ok(())
+            })
+        }
+        "grab" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                let track = match state.per_guild_data.current_track(guild_id) {
+                    Some(val) => val.track,
+                    None => {
+                        response_context.with_content("Nothing is playing").await?;
+                        return Ok(());
+                    }
+                };
+                match send_dm(&state, author_id, embeds::track("Grabbed", &track)).await {
+                    Ok(()) => {
+                        response_context
+                            .with_content("Sent you the track in DMs")
+                            .await?;
+                    }
+                    // Most likely the DMs are closed, reply in place then.
+                    Err(err) => {
+                        debug!(message = "unable to dm the track", ?err);
+                        response_context
+                            .with_embed(embeds::track("Grabbed", &track))
+                            .await?;
+                    }
+                }
+                Ok(())
+            })
+        }
+        "lyrics" => spawn_command(response_context.clone(), async move {
+            let track = match state.per_guild_data.current_track(guild_id) {
+                Some(val) => val.track,
+                None => {
+                    response_context.with_content("Nothing is playing").await?;
+                    return Ok(());
+                }
             };
-            let value = match value.parse() {
-                Ok(value) => value,
-                Err(err) => {
+            let found = match lyrics::search(&state, &track).await {
+                Ok(Some(val)) => val,
+                Ok(None) => {
                     response_context
-                        .with_content(&format!("Position is invalid: {}", err))
+                        .with_content(&format!("No lyrics found for {}", format_track(&track)))
                         .await?;
                     return Ok(());
                 }
+                Err(err) => return Err(err),
             };
-            match action::seek(&state, guild_id, value).await {
-                Ok(val) => {
+            let pages = lyrics::paginate(&found, lyrics::PAGE_LEN);
+            let count = pages.len();
+            for (index, page) in pages.into_iter().enumerate() {
+                response_context
+                    .with_embed(embeds::lyrics(&track, page, index + 1, count))
+                    .await?;
+            }
+            Ok(())
+        }),
+        "announcements" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                if !permissions::can_manage_channels(&state, guild_id, author_id) {
                     response_context
-                        .with_content(&format!("Position was set to {}ms", val))
+                        .with_content("You need the Manage Channels permission to do that")
                         .await?;
-                    Ok(())
+                    return Ok(());
                 }
-                Err(err) => Err(err)?,
+                let announcements = match args.next().as_deref() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => {
+                        response_context
+                            .with_usage("Pass on or off as an argument", "announcements")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                state
+                    .per_guild_data
+                    .set_announcements(guild_id, announcements);
+                response_context
+                    .with_content(if announcements {
+                        "Track announcements are on"
+                    } else {
+                        "Track announcements are off"
+                    })
+                    .await?;
+                Ok(())
+            })
+        }
+        "autoplay" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                if !permissions::can_manage_channels(&state, guild_id, author_id) {
+                    response_context
+                        .with_content("You need the Manage Channels permission to do that")
+                        .await?;
+                    return Ok(());
+                }
+                let autoplay = match args.next().as_deref() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => {
+                        response_context
+                            .with_usage("Pass on or off as an argument", "autoplay")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                state.per_guild_data.set_autoplay(guild_id, autoplay);
+                response_context
+                    .with_content(if autoplay {
+                        "Autoplay is on, related tracks will play when the queue runs out"
+                    } else {
+                        "Autoplay is off"
+                    })
+                    .await?;
+                Ok(())
+            })
+        }
+        "follow" => spawn_command(response_context.clone(), async move {
+            let follow = match args.next().as_deref() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => {
+                    response_context
+                        .with_usage("Pass on or off as an argument", "follow")
+                        .await?;
+                    return Ok(());
+                }
+            };
+            state.per_guild_data.set_follow(guild_id, follow);
+            response_context
+                .with_content(if follow {
+                    "Following is on, I'll move along with whoever played a track last"
+                } else {
+                    "Following is off"
+                })
+                .await?;
+            Ok(())
+        }),
+        "247" => spawn_command(response_context.clone(), async move {
+            let always_on = match args.next().as_deref() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => {
+                    response_context
+                        .with_usage("Pass on or off as an argument", "247")
+                        .await?;
+                    return Ok(());
+                }
+            };
+            state.per_guild_data.set_always_on(guild_id, always_on);
+            if always_on {
+                state.per_guild_data.set_leave_timer(guild_id, None);
+                state.per_guild_data.set_idle_timer(guild_id, None);
             }
+            response_context
+                .with_content(if always_on {
+                    "24/7 mode is on, I'll stay in the voice channel"
+                } else {
+                    "24/7 mode is off"
+                })
+                .await?;
+            Ok(())
         }),
-        "pause" => spawn(async move {
-            match action::pause_toggle(&state, guild_id).await {
-                Ok(val) => {
+        "searchplatform" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                if !permissions::can_manage_channels(&state, guild_id, author_id) {
                     response_context
-                        .with_content(if val { "Paused" } else { "Unpaused" })
+                        .with_content("You need the Manage Channels permission to do that")
                         .await?;
-                    Ok(())
+                    return Ok(());
+                }
+                let platform = match args.next() {
+                    Some(val) => val,
+                    None => {
+                        let platform = state.per_guild_data.get_search_platform(guild_id);
+                        response_context
+                            .with_content(&format!("Searching on {}", platform))
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                match platform.parse() {
+                    Ok(platform) => {
+                        state.per_guild_data.set_search_platform(guild_id, platform);
+                        response_context
+                            .with_content(&format!("Plain queries now search on {}", platform))
+                            .await?;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        response_context
+                            .with_content(&format!("Invalid search platform: {}", err))
+                            .await?;
+                        Ok(())
+                    }
+                }
+            })
+        }
+        "setprefix" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                if !permissions::can_manage_channels(&state, guild_id, author_id) {
+                    response_context
+                        .with_content("You need the Manage Channels permission to do that")
+                        .await?;
+                    return Ok(());
+                }
+                let prefix = match args.next() {
+                    Some(val) if !val.is_empty() => val,
+                    _ => {
+                        response_context
+                            .with_usage("Pass prefix as an argument", "setprefix")
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                match state.per_guild_data.set_prefix(guild_id, prefix.clone()) {
+                    Ok(()) => {
+                        response_context
+                            .with_content(&format!("Prefix was set to {}", prefix))
+                            .await?;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        response_context
+                            .with_content(&format!("Invalid prefix: {}", err))
+                            .await?;
+                        Ok(())
+                    }
+                }
+            })
+        }
+        "setgreeting" => {
+            let author_id = msg.author.id;
+            spawn_command(response_context.clone(), async move {
+                if !permissions::can_manage_channels(&state, guild_id, author_id) {
+                    response_context
+                        .with_content("You need the Manage Channels permission to do that")
+                        .await?;
+                    return Ok(());
+                }
+                let greeting = args.collect::<Vec<_>>().join(" ");
+                if greeting.is_empty() {
+                    response_context
+                        .with_usage("Pass greeting as an argument", "setgreeting")
+                        .await?;
+                    return Ok(());
+                }
+                state.per_guild_data.set_greeting(guild_id, greeting);
+                response_context.with_content("Greeting was set").await?;
+                Ok(())
+            })
+        }
+        "help" => spawn_command(response_context.clone(), async move {
+            response_context
+                .with_embed(embeds::help(&command_prefix))
+                .await?;
+            Ok(())
+        }),
+        "cachestats" => spawn_command(response_context.clone(), async move {
+            let stats = state.track_cache.stats();
+            response_context
+                .with_content(&format!(
+                    "Track cache: {} hits, {} misses, {}/{} entries",
+                    stats.hits, stats.misses, stats.len, stats.capacity
+                ))
+                .await?;
+            Ok(())
+        }),
+        "stats" => spawn_command(response_context.clone(), async move {
+            let mut lines: Vec<String> = state
+                .node_stats
+                .iter()
+                .map(|entry| {
+                    let stats = entry.value();
+                    format!(
+                        "`{}`: {} players ({} playing), CPU load {:.1}%, memory {}/{} MiB",
+                        entry.key(),
+                        stats.players,
+                        stats.playing_players,
+                        stats.cpu.lavalink_load * 100.0,
+                        stats.memory.used / MIB,
+                        stats.memory.allocated / MIB,
+                    )
+                })
+                .collect();
+            lines.sort();
+            let content = if lines.is_empty() {
+                "No stats received from Lavalink yet".to_owned()
+            } else {
+                lines.join("\n")
+            };
+            response_context.with_content(&content).await?;
+            Ok(())
+        }),
+        "reconnect" => spawn_command(response_context.clone(), async move {
+            let mut lines = Vec::new();
+            for host in &state.lavalink_hosts {
+                state.lavalink.disconnect(host.address).await;
+                state.node_stats.remove(host);
+                match connect_lavalink_node(&state, host).await {
+                    Ok(()) => {
+                        info!(message = "reconnected lavalink node", %host);
+                        lines.push(format!("Reconnected `{}`", host));
+                    }
+                    Err(err) => {
+                        warn!(message = "unable to reconnect lavalink node", %host, ?err);
+                        lines.push(format!("Unable to reconnect `{}`: {}", host, err));
+                    }
                 }
-                Err(err) => Err(err)?,
             }
+            response_context.with_content(&lines.join("\n")).await?;
+            Ok(())
         }),
-        "ping" => spawn(async move {
-            response_context.with_content("pong").await?;
+        "ping" => spawn_command(response_context.clone(), async move {
+            let gateway = state
+                .shard
+                .info()
+                .ok()
+                .and_then(|info| info.latency().average())
+                .map(|latency| format!("{}ms", latency.as_millis()))
+                .unwrap_or_else(|| "unknown".to_owned());
+            let lavalink = match lavalink_latency(&state).await {
+                Ok(latency) => format!("{}ms", latency.as_millis()),
+                Err(err) => {
+                    debug!(message = "unable to measure lavalink latency", ?err);
+                    "unknown".to_owned()
+                }
+            };
+            response_context
+                .with_content(&format!("Gateway: {}, Lavalink: {}", gateway, lavalink))
+                .await?;
             Ok(())
         }),
-        _ => {}
+        // Stay silent unless the command looks like a typo.
+        _ => {
+            if let Some(suggestion) = commands::suggest(&command) {
+                let content = format!(
+                    "Unknown command. Did you mean `{}{}`?",
+                    command_prefix, suggestion
+                );
+                spawn(async move {
+                    response_context.with_content(&content).await?;
+                    Ok(())
+                });
+            }
+        }
+    }
+}
+
+async fn send_dm(state: &State, user_id: UserId, embed: Embed) -> Result<(), anyhow::Error> {
+    let channel = state
+        .http
+        .create_private_channel(user_id)
+        .exec()
+        .await?
+        .model()
+        .await?;
+    let embeds = [embed];
+    state
+        .http
+        .create_message(channel.id)
+        .embeds(&embeds)?
+        .exec()
+        .await?;
+    Ok(())
+}
+
+/// Enqueue the tracks resolved from a single link one by one, returning the
+/// number of the enqueued ones.
+async fn enqueue_resolved(
+    state: &State,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    identifiers: impl IntoIterator<Item = String>,
+    author_id: UserId,
+) -> usize {
+    let mut enqueued = 0;
+    for identifier in identifiers {
+        match action::enqueue(state, guild_id, channel_id, &identifier, author_id).await {
+            Ok(_) => enqueued += 1,
+            Err(err) if err.is::<player::QueueFull>() => break,
+            Err(err) => {
+                warn!(message = "unable to enqueue resolved track", %identifier, ?err);
+            }
+        }
     }
+    enqueued
 }
 
-fn process_lavalink_event(state: &Arc<State>, event: IncomingEvent) {
-    trace!(message = "got lavalink event", ?event);
+fn process_lavalink_event(state: &Arc<State>, node: &LavalinkHost, event: IncomingEvent) {
+    trace!(message = "got lavalink event", %node, ?event);
 
     let state = Arc::clone(state);
     match event {
         IncomingEvent::TrackStart(track_start) => {
+            state.metrics.track_played();
             spawn(async move {
                 let guild_id = track_start.guild_id;
+                if !state.per_guild_data.get_announcements(guild_id) {
+                    return Ok(());
+                }
 
                 let per_guild_info =
                     match state.per_guild_data.get_associated_text_channel(guild_id) {
@@ -331,24 +2209,106 @@ fn process_lavalink_event(state: &Arc<State>, event: IncomingEvent) {
                         }
                     };
 
-                let message = format!("Playing the track");
-
-                state
-                    .http
-                    .create_message(per_guild_info)
-                    .content(&message)
-                    .unwrap()
-                    .exec()
-                    .await?;
+                match state.per_guild_data.current_track(guild_id) {
+                    Some(track) => {
+                        let embeds = [embeds::now_playing(&track, 0)];
+                        let components = interactions::playback_buttons();
+                        let message = state
+                            .http
+                            .create_message(per_guild_info)
+                            .embeds(&embeds)?
+                            .components(&components)?
+                            .exec()
+                            .await?
+                            .model()
+                            .await?;
+                        state
+                            .per_guild_data
+                            .set_now_playing_message(guild_id, Some((per_guild_info, message.id)));
+                        if let Some(interval) = state.progress_update_interval {
+                            let updater = spawn_with_handle(update_progress(
+                                Arc::clone(&state),
+                                guild_id,
+                                track,
+                                interval,
+                            ));
+                            state
+                                .per_guild_data
+                                .set_progress_updater(guild_id, Some(updater));
+                        }
+                    }
+                    None => {
+                        helper::respond_to(&state, per_guild_info, "Playing the track").await?;
+                    }
+                }
 
                 Ok(())
             });
         }
         IncomingEvent::TrackEnd(track_end) => {
+            state
+                .per_guild_data
+                .set_now_playing_message(track_end.guild_id, None);
+            state
+                .per_guild_data
+                .set_progress_updater(track_end.guild_id, None);
+
+            // Only advance the queue when the track ended on its own, stops
+            // and replacements are handled by the commands that issued them.
+            let failed = match track_end.reason.as_str() {
+                "FINISHED" => false,
+                "LOAD_FAILED" => true,
+                reason => {
+                    debug!(message = "not advancing the queue", %reason);
+                    return;
+                }
+            };
+
             spawn(async move {
                 let guild_id = track_end.guild_id;
+                let _guard = state.lock_guild(guild_id).await;
+                let announce = state.per_guild_data.get_announcements(guild_id);
+
+                let finished_track = state.per_guild_data.current_track(guild_id);
+                if failed {
+                    let channel_id = state
+                        .per_guild_data
+                        .get_associated_text_channel(guild_id)
+                        .filter(|_| announce);
+                    if let (Some(track), Some(channel_id)) = (&finished_track, channel_id) {
+                        let message =
+                            format!("Track failed: {}, skipping", format_track(&track.track));
+                        helper::respond_to(&state, channel_id, &message).await?;
+                    }
+                } else if let Some(track) = finished_track.clone() {
+                    // Loop the track only if it has played till the end.
+                    state
+                        .per_guild_data
+                        .with_track_manger(guild_id, |track_manager| {
+                            track_manager.requeue_finished(track)
+                        });
+                }
 
-                let track = action::play_from_queue(&state, guild_id).await?;
+                let mut heading = "Playing from queue";
+                let mut track = action::play_from_queue(&state, guild_id).await?;
+                if let (None, Some(finished_track)) = (&track, &finished_track) {
+                    if state.per_guild_data.get_autoplay(guild_id) {
+                        heading = "Autoplaying";
+                        track = match action::autoplay(&state, guild_id, finished_track).await {
+                            Ok(val) => val,
+                            Err(err) => {
+                                warn!(message = "unable to autoplay", %guild_id, ?err);
+                                None
+                            }
+                        };
+                    }
+                }
+                if track.is_none() {
+                    auto_leave::start_idle_timer(&state, guild_id);
+                }
+                if !announce {
+                    return Ok(());
+                }
 
                 let per_guild_info =
                     match state.per_guild_data.get_associated_text_channel(guild_id) {
@@ -362,26 +2322,116 @@ fn process_lavalink_event(state: &Arc<State>, event: IncomingEvent) {
                         }
                     };
 
-                let message = match track {
-                    Some(track) => format!("Playing {} from queue", format_track(&track)),
-                    None => format!("Queue empty"),
-                };
+                match track {
+                    Some(track) => {
+                        let embeds = [embeds::queued_track(heading, &track)];
+                        state
+                            .http
+                            .create_message(per_guild_info)
+                            .embeds(&embeds)?
+                            .exec()
+                            .await?;
+                    }
+                    // Stay silent when sticking around in the 24/7 mode.
+                    None if state.per_guild_data.get_always_on(guild_id) => {}
+                    None => {
+                        helper::respond_to(&state, per_guild_info, "Queue empty").await?;
+                    }
+                }
 
-                state
-                    .http
-                    .create_message(per_guild_info)
-                    .content(&message)
-                    .unwrap()
-                    .exec()
-                    .await?;
+                Ok(())
+            });
+        }
+        // Lavalink reports the failed and stuck tracks with the
+        // `TrackExceptionEvent` and `TrackStuckEvent`, but twilight-lavalink
+        // doesn't parse those. The exceptions still end the track with
+        // `LOAD_FAILED`, handled above, while a stuck track is only noticed
+        // here as its position not moving while playing, which can't tell
+        // a stalled stream from a slow node.
+        IncomingEvent::PlayerUpdate(player_update) => {
+            let guild_id = player_update.guild_id;
+            let playing = state
+                .lavalink
+                .players()
+                .get(&guild_id)
+                .map(|player| !player.paused())
+                .unwrap_or(false)
+                && state.per_guild_data.current_track(guild_id).is_some();
+            let position = Some(player_update.state.position).filter(|_| playing);
+            if !state
+                .per_guild_data
+                .observe_position(guild_id, position, TRACK_STUCK_THRESHOLD)
+            {
+                return;
+            }
+
+            spawn(async move {
+                let track = state.per_guild_data.current_track(guild_id);
+                warn!(message = "track is stuck, skipping", %guild_id, ?track);
+
+                let channel_id = state
+                    .per_guild_data
+                    .get_associated_text_channel(guild_id)
+                    .filter(|_| state.per_guild_data.get_announcements(guild_id));
+                if let (Some(track), Some(channel_id)) = (&track, channel_id) {
+                    let message =
+                        format!("Track is stuck: {}, skipping", format_track(&track.track));
+                    helper::respond_to(&state, channel_id, &message).await?;
+                }
 
+                let _guard = state.lock_guild(guild_id).await;
+                action::skip(&state, guild_id).await?;
                 Ok(())
             });
         }
+        IncomingEvent::Stats(stats) => {
+            state.node_stats.insert(node.clone(), stats);
+        }
         _ => {}
     }
 }
 
+/// Keep editing the now playing message with the track position.
+async fn update_progress(
+    state: Arc<State>,
+    guild_id: GuildId,
+    track: player::QueuedTrack,
+    interval: Duration,
+) -> Result<(), anyhow::Error> {
+    let mut interval = tokio::time::interval(interval);
+    // The first tick completes immediately, the message is fresh by then.
+    interval.tick().await;
+    // Refresh once more after pausing, then keep the position frozen.
+    let mut frozen = false;
+    loop {
+        interval.tick().await;
+
+        let (channel_id, message_id) = match state.per_guild_data.now_playing_message(guild_id) {
+            Some(val) => val,
+            None => return Ok(()),
+        };
+        let paused = state.per_guild_data.is_paused(guild_id);
+        if paused && frozen {
+            continue;
+        }
+        frozen = paused;
+        let position = match state.lavalink.players().get(&guild_id) {
+            Some(player) => player.position(),
+            None => return Ok(()),
+        };
+
+        let embeds = [embeds::now_playing(&track, position)];
+        state
+            .http
+            .update_message(channel_id, message_id)
+            .embeds(&embeds)?
+            .exec()
+            .await?;
+    }
+}
+
+const TRACK_STUCK_THRESHOLD: Duration = Duration::from_secs(15);
+
 fn format_track(track: &Track) -> String {
     format!(
         "**{}** by **{}**",
@@ -389,3 +2439,113 @@ fn format_track(track: &Track) -> String {
         track.info.author.as_deref().unwrap_or(""),
     )
 }
+
+fn format_progress(position: i64, length: i64) -> String {
+    const WIDTH: i64 = 20;
+    let filled = if length > 0 {
+        (position.clamp(0, length) * WIDTH / length) as usize
+    } else {
+        0
+    };
+    format!(
+        "{}{} {} / {}",
+        "▬".repeat(filled),
+        "▭".repeat(WIDTH as usize - filled),
+        format_duration(position),
+        format_duration(length),
+    )
+}
+
+fn format_play_heading(range: parser::PlayRange) -> String {
+    match (range.start, range.end) {
+        (Some(start), Some(end)) => format!(
+            "Playing from {} to {}",
+            format_duration(start),
+            format_duration(end)
+        ),
+        (Some(start), None) => format!("Playing from {}", format_duration(start)),
+        _ => "Playing".to_owned(),
+    }
+}
+
+fn format_duration(millis: i64) -> String {
+    let seconds = millis.max(0) / 1000;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+fn format_length(track: &Track) -> String {
+    if track.info.is_stream {
+        "LIVE".to_owned()
+    } else {
+        format_duration(track.info.length as i64)
+    }
+}
+
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+const QUEUE_PAGE_LEN: usize = 10;
+
+const MIB: u64 = 1024 * 1024;
+
+fn format_queued_track(queued_track: &player::QueuedTrack) -> String {
+    format!(
+        "{} requested by <@{}>",
+        format_track(&queued_track.track),
+        queued_track.requested_by
+    )
+}
+
+fn format_queue<'a>(
+    tracks: impl ExactSizeIterator<Item = &'a player::QueuedTrack> + Clone,
+    page: usize,
+) -> String {
+    let total = tracks.len();
+    if total == 0 {
+        return "Queue is empty".to_owned();
+    }
+
+    // Streams have no meaningful length.
+    let total_length: i64 = tracks
+        .clone()
+        .filter(|track| !track.track.info.is_stream)
+        .map(|track| track.track.info.length as i64)
+        .sum();
+
+    let pages = queue_page_count(total);
+    let page = page.min(pages - 1);
+    let mut lines: Vec<String> = tracks
+        .enumerate()
+        .skip(page * QUEUE_PAGE_LEN)
+        .take(QUEUE_PAGE_LEN)
+        .map(|(index, track)| {
+            format!(
+                "{}. {} [{}]",
+                index + 1,
+                format_queued_track(track),
+                format_length(&track.track)
+            )
+        })
+        .collect();
+    lines.push(format!(
+        "Total queue time: {}",
+        format_duration(total_length)
+    ));
+    if pages > 1 {
+        lines.push(format!(
+            "Page {}/{}, {} tracks total",
+            page + 1,
+            pages,
+            total
+        ));
+    }
+    lines.join("\n")
+}
+
+fn queue_page_count(total: usize) -> usize {
+    ((total + QUEUE_PAGE_LEN - 1) / QUEUE_PAGE_LEN).max(1)
+}
@@ -0,0 +1,115 @@
+use crate::State;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use std::{
+    convert::Infallible,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    commands_processed: AtomicU64,
+    tracks_played: AtomicU64,
+}
+
+impl Metrics {
+    pub fn command_processed(&self) {
+        self.commands_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn track_played(&self) {
+        self.tracks_played.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub async fn serve(state: Arc<State>, addr: SocketAddr) -> Result<(), anyhow::Error> {
+    let make_service = make_service_fn(move |_| {
+        let state = Arc::clone(&state);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = Arc::clone(&state);
+                async move { Ok::<_, Infallible>(handle(&state, req)) }
+            }))
+        }
+    });
+    Server::bind(&addr).serve(make_service).await?;
+    Ok(())
+}
+
+fn handle(state: &State, req: Request<Body>) -> Response<Body> {
+    if req.uri().path() != "/metrics" {
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = StatusCode::NOT_FOUND;
+        return res;
+    }
+    Response::new(Body::from(render(state)))
+}
+
+fn render(state: &State) -> String {
+    let guild_ids = state.per_guild_data.guild_ids();
+    let active_players = guild_ids
+        .iter()
+        .filter(|guild_id| state.lavalink.players().get(guild_id).is_some())
+        .count();
+
+    let mut out = String::new();
+    write_metric(
+        &mut out,
+        "musicbot_commands_processed_total",
+        "counter",
+        "Number of processed commands.",
+    );
+    writeln!(
+        out,
+        "musicbot_commands_processed_total {}",
+        state.metrics.commands_processed.load(Ordering::Relaxed)
+    )
+    .unwrap();
+    write_metric(
+        &mut out,
+        "musicbot_tracks_played_total",
+        "counter",
+        "Number of tracks started playing.",
+    );
+    writeln!(
+        out,
+        "musicbot_tracks_played_total {}",
+        state.metrics.tracks_played.load(Ordering::Relaxed)
+    )
+    .unwrap();
+    write_metric(
+        &mut out,
+        "musicbot_active_players",
+        "gauge",
+        "Number of active players.",
+    );
+    writeln!(out, "musicbot_active_players {}", active_players).unwrap();
+    write_metric(
+        &mut out,
+        "musicbot_queue_depth",
+        "gauge",
+        "Number of queued tracks per guild.",
+    );
+    for guild_id in guild_ids {
+        let depth = state.per_guild_data.queue_len(guild_id);
+        writeln!(
+            out,
+            "musicbot_queue_depth{{guild_id=\"{}\"}} {}",
+            guild_id, depth
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+}
@@ -0,0 +1,252 @@
+use crate::player::SearchPlatform;
+use thiserror::Error;
+use twilight_model::id::RoleId;
+
+/// Split the command line into arguments by whitespace, keeping the double
+/// quoted parts together; an unbalanced quote extends till the end of input.
+pub fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut in_quotes = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+/// Build the track identifier from the arguments: URLs are passed verbatim,
+/// everything else is joined into a search query on the platform.
+pub fn track_identifier<I>(args: I, platform: SearchPlatform) -> Option<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let args: Vec<String> = args.into_iter().collect();
+    match args.as_slice() {
+        [] => None,
+        [url, ..] if url.starts_with("http") => Some(url.clone()),
+        _ => Some(format!("{}{}", platform.prefix(), args.join(" "))),
+    }
+}
+
+/// The part of the track to play, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlayRange {
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+}
+
+/// Split off the `at <timestamp>` or `from <timestamp> to <timestamp>`
+/// suffix, returning the rest of the arguments and the range to play. Only
+/// links take the suffix, as the search queries can end like that too.
+pub fn play_range(mut args: Vec<String>) -> (Vec<String>, PlayRange) {
+    let (suffix_len, range) = match args.as_slice() {
+        [.., from, start, to, end] if from == "from" && to == "to" => {
+            match (parse_timestamp(start), parse_timestamp(end)) {
+                (Ok(start), Ok(end)) => (
+                    4,
+                    PlayRange {
+                        start: Some(start),
+                        end: Some(end),
+                    },
+                ),
+                _ => (0, PlayRange::default()),
+            }
+        }
+        [.., at, start] if at == "at" => match parse_timestamp(start) {
+            Ok(start) => (
+                2,
+                PlayRange {
+                    start: Some(start),
+                    end: None,
+                },
+            ),
+            Err(_) => (0, PlayRange::default()),
+        },
+        _ => (0, PlayRange::default()),
+    };
+    match args.first() {
+        Some(url) if url.starts_with("http") && args.len() - suffix_len == 1 => {
+            args.truncate(1);
+            (args, range)
+        }
+        _ => (args, PlayRange::default()),
+    }
+}
+
+/// Parse a role mention, like `<@&123>`, or a bare role id.
+pub fn role_id(input: &str) -> Option<RoleId> {
+    let id = input
+        .strip_prefix("<@&")
+        .and_then(|val| val.strip_suffix('>'))
+        .unwrap_or(input);
+    RoleId::new(id.parse().ok()?)
+}
+
+/// Parse a timestamp in seconds, `mm:ss` or `hh:mm:ss` format into
+/// milliseconds.
+pub fn parse_timestamp(input: &str) -> Result<i64, ParseTimestampError> {
+    let parts = input
+        .split(':')
+        .map(|part| part.parse::<i64>().map_err(|_| ParseTimestampError))
+        .collect::<Result<Vec<_>, _>>()?;
+    if parts.iter().any(|part| *part < 0) {
+        return Err(ParseTimestampError);
+    }
+
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [seconds] => (0, 0, *seconds),
+        [minutes, seconds] if *seconds < 60 => (0, *minutes, *seconds),
+        [hours, minutes, seconds] if *minutes < 60 && *seconds < 60 => (*hours, *minutes, *seconds),
+        _ => return Err(ParseTimestampError),
+    };
+    hours
+        .checked_mul(60)
+        .and_then(|val| val.checked_add(minutes))
+        .and_then(|val| val.checked_mul(60))
+        .and_then(|val| val.checked_add(seconds))
+        .and_then(|val| val.checked_mul(1000))
+        .ok_or(ParseTimestampError)
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error("timestamp must be in seconds, mm:ss or hh:mm:ss format")]
+pub struct ParseTimestampError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unquoted() {
+        assert_eq!(tokenize("play  some track "), vec!["play", "some", "track"]);
+    }
+
+    #[test]
+    fn quoted() {
+        assert_eq!(
+            tokenize(r#"play "never gonna give you up" now"#),
+            vec!["play", "never gonna give you up", "now"]
+        );
+        assert_eq!(tokenize(r#"play """#), vec!["play", ""]);
+    }
+
+    #[test]
+    fn unbalanced_quote() {
+        assert_eq!(
+            tokenize(r#"play "never gonna  give"#),
+            vec!["play", "never gonna  give"]
+        );
+    }
+
+    #[test]
+    fn play_range_start() {
+        let args = tokenize("https://example.com/track at 1:30");
+        assert_eq!(
+            play_range(args),
+            (
+                vec!["https://example.com/track".to_owned()],
+                PlayRange {
+                    start: Some(90_000),
+                    end: None
+                }
+            )
+        );
+
+        let args = tokenize("live at wembley");
+        assert_eq!(play_range(args.clone()), (args, PlayRange::default()));
+
+        let args = tokenize("live at 5:00");
+        assert_eq!(play_range(args.clone()), (args, PlayRange::default()));
+    }
+
+    #[test]
+    fn play_range_start_and_end() {
+        let args = tokenize("https://example.com/track from 0:30 to 1:00");
+        assert_eq!(
+            play_range(args),
+            (
+                vec!["https://example.com/track".to_owned()],
+                PlayRange {
+                    start: Some(30_000),
+                    end: Some(60_000)
+                }
+            )
+        );
+
+        let args = tokenize("from dusk to dawn");
+        assert_eq!(play_range(args.clone()), (args, PlayRange::default()));
+
+        let args = tokenize("song from 0:30 to 1:00");
+        assert_eq!(play_range(args.clone()), (args, PlayRange::default()));
+    }
+
+    #[test]
+    fn timestamp_seconds() {
+        assert_eq!(parse_timestamp("0"), Ok(0));
+        assert_eq!(parse_timestamp("83"), Ok(83_000));
+    }
+
+    #[test]
+    fn timestamp_minutes() {
+        assert_eq!(parse_timestamp("1:23"), Ok(83_000));
+        assert_eq!(parse_timestamp("01:05"), Ok(65_000));
+        assert_eq!(parse_timestamp("90:00"), Ok(5_400_000));
+    }
+
+    #[test]
+    fn timestamp_hours() {
+        assert_eq!(parse_timestamp("1:02:03"), Ok(3_723_000));
+    }
+
+    #[test]
+    fn timestamp_overflow() {
+        for input in &[
+            "9223372036854776",
+            "9999999999999999:00",
+            "9999999999999999:00:00",
+            "2562047788015:12:56",
+        ] {
+            assert_eq!(
+                parse_timestamp(input),
+                Err(ParseTimestampError),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn timestamp_invalid() {
+        for input in &[
+            "", "abc", "1:", ":30", "1:60", "1:60:00", "-1:00", "1:2:3:4",
+        ] {
+            assert_eq!(
+                parse_timestamp(input),
+                Err(ParseTimestampError),
+                "{}",
+                input
+            );
+        }
+    }
+}
@@ -1,23 +1,84 @@
-use twilight_model::id::{ChannelId, GuildId};
+use dashmap::mapref::one::RefMut;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use tokio::{sync::Notify, task::JoinHandle};
+use twilight_lavalink::http::Track;
+use twilight_model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
 
 use crate::player;
 
-#[derive(Debug, Default)]
+const MAX_PREFIX_LEN: usize = 5;
+const DEFAULT_VOLUME: i64 = 100;
+const DEFAULT_SAME_CHANNEL_REQUIRED: bool = true;
+const DEFAULT_ANNOUNCEMENTS: bool = true;
+const DEFAULT_GREETING: &str = "Hi there, ready to play some music";
+const MAX_HISTORY_LEN: usize = 50;
+
+/// The queued tracks of each guild, the current track goes first.
+pub type QueueSnapshot = HashMap<GuildId, Vec<player::QueuedTrack>>;
+
+#[derive(Debug)]
 pub struct Store {
     map: dashmap::DashMap<GuildId, PerGuildData>,
+    default_prefix: String,
+    max_queue_len: usize,
+    default_search_platform: player::SearchPlatform,
+    queues_changed: Notify,
 }
 
 impl Store {
-    pub fn associate_text_channel(&self, guild_id: GuildId, channel_id: ChannelId) {
+    pub fn new(
+        default_prefix: String,
+        max_queue_len: usize,
+        default_search_platform: player::SearchPlatform,
+    ) -> Self {
+        Self {
+            map: Default::default(),
+            default_prefix,
+            max_queue_len,
+            default_search_platform,
+            queues_changed: Notify::new(),
+        }
+    }
+
+    pub fn snapshot(&self) -> QueueSnapshot {
         self.map
-            .entry(guild_id)
-            .and_modify(|data| {
-                data.associated_text_channel = Some(channel_id);
+            .iter()
+            .map(|entry| {
+                let tracks = entry
+                    .current_track
+                    .iter()
+                    .chain(entry.track_manager.list())
+                    .cloned()
+                    .collect();
+                (*entry.key(), tracks)
             })
-            .or_insert_with(|| PerGuildData {
-                associated_text_channel: Some(channel_id),
-                ..Default::default()
-            });
+            .filter(|(_, tracks): &(GuildId, Vec<player::QueuedTrack>)| !tracks.is_empty())
+            .collect()
+    }
+
+    pub fn restore(&self, snapshot: QueueSnapshot) {
+        for (guild_id, tracks) in snapshot {
+            // The tracks beyond the queue length limit are dropped.
+            self.with_track_manger(guild_id, |track_manager| track_manager.enqueue(tracks).ok());
+        }
+    }
+
+    /// Wait until the queues might have changed since the last call.
+    pub async fn queues_changed(&self) {
+        self.queues_changed.notified().await
+    }
+
+    pub fn guild_ids(&self) -> Vec<GuildId> {
+        self.map.iter().map(|entry| *entry.key()).collect()
+    }
+
+    pub fn associate_text_channel(&self, guild_id: GuildId, channel_id: ChannelId) {
+        let mut data = self.entry(guild_id);
+        data.associated_text_channel = Some(channel_id);
     }
 
     pub fn get_associated_text_channel(&self, guild_id: GuildId) -> Option<ChannelId> {
@@ -25,12 +86,366 @@ impl Store {
         data.associated_text_channel.clone()
     }
 
+    pub fn get_prefix(&self, guild_id: GuildId) -> String {
+        self.map
+            .get(&guild_id)
+            .and_then(|data| data.prefix.clone())
+            .unwrap_or_else(|| self.default_prefix.clone())
+    }
+
+    pub fn set_prefix(&self, guild_id: GuildId, prefix: String) -> Result<(), PrefixTooLong> {
+        if prefix.chars().count() > MAX_PREFIX_LEN {
+            return Err(PrefixTooLong {
+                max_len: MAX_PREFIX_LEN,
+            });
+        }
+
+        let mut data = self.entry(guild_id);
+        data.prefix = Some(prefix);
+        Ok(())
+    }
+
+    pub fn current_track(&self, guild_id: GuildId) -> Option<player::QueuedTrack> {
+        let data = self.map.get(&guild_id)?;
+        data.current_track.clone()
+    }
+
+    pub fn set_current_track(&self, guild_id: GuildId, track: Option<player::QueuedTrack>) {
+        {
+            let mut data = self.entry(guild_id);
+            data.current_track = track;
+            // Tracks start playing right away.
+            data.paused = false;
+        }
+        self.queues_changed.notify_one();
+    }
+
+    pub fn is_paused(&self, guild_id: GuildId) -> bool {
+        self.map
+            .get(&guild_id)
+            .map(|data| data.paused)
+            .unwrap_or(false)
+    }
+
+    pub fn set_paused(&self, guild_id: GuildId, paused: bool) {
+        let mut data = self.entry(guild_id);
+        data.paused = paused;
+    }
+
+    /// Record the reported position of the playing track, `None` when
+    /// nothing is playing. Returns `true` if the position hasn't moved for
+    /// the `threshold`, restarting the measurement.
+    pub fn observe_position(
+        &self,
+        guild_id: GuildId,
+        position: Option<i64>,
+        threshold: Duration,
+    ) -> bool {
+        let mut data = self.entry(guild_id);
+        data.position_watch
+            .observe(position, Instant::now(), threshold)
+    }
+
+    /// The recently played tracks, the newest first.
+    pub fn history(&self, guild_id: GuildId) -> Vec<Track> {
+        self.map
+            .get(&guild_id)
+            .map(|data| data.history.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn last_played(&self, guild_id: GuildId) -> Option<Track> {
+        let data = self.map.get(&guild_id)?;
+        data.history.back().cloned()
+    }
+
+    /// Take the track played before the current one off the history, along
+    /// with the current one, so that going back repeatedly walks the history.
+    pub fn take_previous(&self, guild_id: GuildId) -> Option<Track> {
+        let mut data = self.map.get_mut(&guild_id)?;
+        // The current track is in the history already, as it is pushed when
+        // the track starts.
+        let current = usize::from(data.current_track.is_some());
+        if data.history.len() <= current {
+            return None;
+        }
+        let len = data.history.len() - current;
+        data.history.truncate(len);
+        data.history.pop_back()
+    }
+
+    pub fn push_history(&self, guild_id: GuildId, track: Track) {
+        let mut data = self.entry(guild_id);
+        if data.history.len() >= MAX_HISTORY_LEN {
+            data.history.pop_front();
+        }
+        data.history.push_back(track);
+    }
+
+    pub fn get_voice_channel(&self, guild_id: GuildId) -> Option<ChannelId> {
+        let data = self.map.get(&guild_id)?;
+        data.voice_channel
+    }
+
+    /// Set the voice channel the bot is in, returning the previous one.
+    pub fn set_voice_channel(
+        &self,
+        guild_id: GuildId,
+        channel_id: Option<ChannelId>,
+    ) -> Option<ChannelId> {
+        let mut data = self.entry(guild_id);
+        std::mem::replace(&mut data.voice_channel, channel_id)
+    }
+
+    pub fn get_same_channel_required(&self, guild_id: GuildId) -> bool {
+        self.map
+            .get(&guild_id)
+            .and_then(|data| data.same_channel_required)
+            .unwrap_or(DEFAULT_SAME_CHANNEL_REQUIRED)
+    }
+
+    pub fn set_same_channel_required(&self, guild_id: GuildId, required: bool) {
+        let mut data = self.entry(guild_id);
+        data.same_channel_required = Some(required);
+    }
+
+    pub fn get_search_platform(&self, guild_id: GuildId) -> player::SearchPlatform {
+        self.map
+            .get(&guild_id)
+            .and_then(|data| data.search_platform)
+            .unwrap_or(self.default_search_platform)
+    }
+
+    pub fn set_search_platform(&self, guild_id: GuildId, platform: player::SearchPlatform) {
+        let mut data = self.entry(guild_id);
+        data.search_platform = Some(platform);
+    }
+
+    pub fn get_announcements(&self, guild_id: GuildId) -> bool {
+        self.map
+            .get(&guild_id)
+            .and_then(|data| data.announcements)
+            .unwrap_or(DEFAULT_ANNOUNCEMENTS)
+    }
+
+    pub fn set_announcements(&self, guild_id: GuildId, announcements: bool) {
+        let mut data = self.entry(guild_id);
+        data.announcements = Some(announcements);
+    }
+
+    pub fn get_greeting(&self, guild_id: GuildId) -> String {
+        self.map
+            .get(&guild_id)
+            .and_then(|data| data.greeting.clone())
+            .unwrap_or_else(|| DEFAULT_GREETING.to_owned())
+    }
+
+    pub fn set_greeting(&self, guild_id: GuildId, greeting: String) {
+        let mut data = self.entry(guild_id);
+        data.greeting = Some(greeting);
+    }
+
+    pub fn get_autoplay(&self, guild_id: GuildId) -> bool {
+        self.map
+            .get(&guild_id)
+            .map(|data| data.autoplay)
+            .unwrap_or(false)
+    }
+
+    pub fn set_autoplay(&self, guild_id: GuildId, autoplay: bool) {
+        let mut data = self.entry(guild_id);
+        data.autoplay = autoplay;
+    }
+
+    pub fn get_follow(&self, guild_id: GuildId) -> bool {
+        self.map
+            .get(&guild_id)
+            .map(|data| data.follow)
+            .unwrap_or(false)
+    }
+
+    pub fn set_follow(&self, guild_id: GuildId, follow: bool) {
+        let mut data = self.entry(guild_id);
+        data.follow = follow;
+    }
+
+    /// The user who played a track last, the one to follow around.
+    pub fn get_controller(&self, guild_id: GuildId) -> Option<UserId> {
+        let data = self.map.get(&guild_id)?;
+        data.controller
+    }
+
+    pub fn set_controller(&self, guild_id: GuildId, user_id: UserId) {
+        let mut data = self.entry(guild_id);
+        data.controller = Some(user_id);
+    }
+
+    pub fn get_always_on(&self, guild_id: GuildId) -> bool {
+        self.map
+            .get(&guild_id)
+            .map(|data| data.always_on)
+            .unwrap_or(false)
+    }
+
+    pub fn set_always_on(&self, guild_id: GuildId, always_on: bool) {
+        let mut data = self.entry(guild_id);
+        data.always_on = always_on;
+    }
+
+    pub fn get_dj_role(&self, guild_id: GuildId) -> Option<RoleId> {
+        let data = self.map.get(&guild_id)?;
+        data.dj_role
+    }
+
+    pub fn set_dj_role(&self, guild_id: GuildId, role_id: Option<RoleId>) {
+        let mut data = self.entry(guild_id);
+        data.dj_role = role_id;
+    }
+
+    pub fn get_volume(&self, guild_id: GuildId) -> i64 {
+        self.map
+            .get(&guild_id)
+            .and_then(|data| data.volume)
+            .unwrap_or(DEFAULT_VOLUME)
+    }
+
+    pub fn set_volume(&self, guild_id: GuildId, volume: i64) {
+        let mut data = self.entry(guild_id);
+        data.volume = Some(volume);
+    }
+
+    pub fn get_bass_preset(&self, guild_id: GuildId) -> player::BassPreset {
+        self.map
+            .get(&guild_id)
+            .map(|data| data.bass_preset)
+            .unwrap_or_default()
+    }
+
+    pub fn set_bass_preset(&self, guild_id: GuildId, preset: player::BassPreset) {
+        let mut data = self.entry(guild_id);
+        data.bass_preset = preset;
+    }
+
+    pub fn get_filters_preset(&self, guild_id: GuildId) -> player::FiltersPreset {
+        self.map
+            .get(&guild_id)
+            .map(|data| data.filters_preset)
+            .unwrap_or_default()
+    }
+
+    pub fn set_filters_preset(&self, guild_id: GuildId, preset: player::FiltersPreset) {
+        let mut data = self.entry(guild_id);
+        data.filters_preset = preset;
+    }
+
+    pub fn now_playing_message(&self, guild_id: GuildId) -> Option<(ChannelId, MessageId)> {
+        let data = self.map.get(&guild_id)?;
+        data.now_playing_message
+    }
+
+    pub fn set_now_playing_message(
+        &self,
+        guild_id: GuildId,
+        message: Option<(ChannelId, MessageId)>,
+    ) {
+        let mut data = self.entry(guild_id);
+        data.now_playing_message = message;
+    }
+
+    pub fn set_progress_updater(&self, guild_id: GuildId, updater: Option<JoinHandle<()>>) {
+        self.replace_timer(guild_id, |data| &mut data.progress_updater, updater)
+    }
+
+    pub fn has_leave_timer(&self, guild_id: GuildId) -> bool {
+        self.map
+            .get(&guild_id)
+            .map(|data| data.leave_timer.is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn set_leave_timer(&self, guild_id: GuildId, timer: Option<JoinHandle<()>>) {
+        self.replace_timer(guild_id, |data| &mut data.leave_timer, timer)
+    }
+
+    pub fn take_leave_timer(&self, guild_id: GuildId) -> Option<JoinHandle<()>> {
+        let mut data = self.map.get_mut(&guild_id)?;
+        data.leave_timer.take()
+    }
+
+    pub fn set_idle_timer(&self, guild_id: GuildId, timer: Option<JoinHandle<()>>) {
+        self.replace_timer(guild_id, |data| &mut data.idle_timer, timer)
+    }
+
+    pub fn take_idle_timer(&self, guild_id: GuildId) -> Option<JoinHandle<()>> {
+        let mut data = self.map.get_mut(&guild_id)?;
+        data.idle_timer.take()
+    }
+
+    pub fn set_fader(&self, guild_id: GuildId, fader: Option<JoinHandle<()>>) {
+        self.replace_timer(guild_id, |data| &mut data.fader, fader)
+    }
+
+    pub fn set_resume_timer(&self, guild_id: GuildId, timer: Option<JoinHandle<()>>) {
+        self.replace_timer(guild_id, |data| &mut data.resume_timer, timer)
+    }
+
+    pub fn take_resume_timer(&self, guild_id: GuildId) -> Option<JoinHandle<()>> {
+        let mut data = self.map.get_mut(&guild_id)?;
+        data.resume_timer.take()
+    }
+
+    /// Replace the timer in the selected slot, aborting the previous one.
+    fn replace_timer<S>(&self, guild_id: GuildId, select: S, timer: Option<JoinHandle<()>>)
+    where
+        S: FnOnce(&mut PerGuildData) -> &mut Option<JoinHandle<()>>,
+    {
+        let prev = {
+            let mut data = self.entry(guild_id);
+            std::mem::replace(select(&mut data), timer)
+        };
+        if let Some(prev) = prev {
+            prev.abort();
+        }
+    }
+
+    pub fn max_queue_len(&self) -> usize {
+        self.max_queue_len
+    }
+
+    pub fn queue_len(&self, guild_id: GuildId) -> usize {
+        self.map
+            .get(&guild_id)
+            .map(|data| data.track_manager.list().len())
+            .unwrap_or(0)
+    }
+
+    fn entry(&self, guild_id: GuildId) -> RefMut<'_, GuildId, PerGuildData> {
+        self.map.entry(guild_id).or_insert_with(|| PerGuildData {
+            track_manager: player::TrackManager::with_max_len(self.max_queue_len),
+            ..Default::default()
+        })
+    }
+
+    /// Like `with_track_manger`, but for reading the queue, so it isn't
+    /// persisted again.
+    pub fn read_track_manager<F, V>(&self, guild_id: GuildId, f: F) -> V
+    where
+        F: FnOnce(&player::TrackManager) -> V,
+    {
+        let data = self.entry(guild_id);
+        f(&data.track_manager)
+    }
+
     pub fn with_track_manger<F, V>(&self, guild_id: GuildId, f: F) -> V
     where
         F: FnOnce(&mut player::TrackManager) -> V,
     {
-        let mut data = self.map.entry(guild_id).or_default();
-        f(&mut data.track_manager)
+        let val = {
+            let mut data = self.entry(guild_id);
+            f(&mut data.track_manager)
+        };
+        self.queues_changed.notify_one();
+        val
     }
 }
 
@@ -38,4 +453,34 @@ impl Store {
 struct PerGuildData {
     pub associated_text_channel: Option<ChannelId>,
     pub track_manager: player::TrackManager,
+    pub prefix: Option<String>,
+    pub current_track: Option<player::QueuedTrack>,
+    pub paused: bool,
+    pub history: VecDeque<Track>,
+    pub position_watch: player::PositionWatch,
+    pub leave_timer: Option<JoinHandle<()>>,
+    pub idle_timer: Option<JoinHandle<()>>,
+    pub resume_timer: Option<JoinHandle<()>>,
+    pub fader: Option<JoinHandle<()>>,
+    pub now_playing_message: Option<(ChannelId, MessageId)>,
+    pub progress_updater: Option<JoinHandle<()>>,
+    pub volume: Option<i64>,
+    pub bass_preset: player::BassPreset,
+    pub filters_preset: player::FiltersPreset,
+    pub voice_channel: Option<ChannelId>,
+    pub dj_role: Option<RoleId>,
+    pub same_channel_required: Option<bool>,
+    pub autoplay: bool,
+    pub always_on: bool,
+    pub follow: bool,
+    pub controller: Option<UserId>,
+    pub search_platform: Option<player::SearchPlatform>,
+    pub announcements: Option<bool>,
+    pub greeting: Option<String>,
+}
+
+#[derive(Debug, Error)]
+#[error("prefix is too long, must be at most {max_len} characters")]
+pub struct PrefixTooLong {
+    max_len: usize,
 }
@@ -0,0 +1,72 @@
+use crate::State;
+use twilight_model::{
+    guild::Permissions,
+    id::{GuildId, RoleId, UserId},
+};
+
+/// Check whether the member is allowed to use the DJ-only commands: either
+/// the guild has no DJ role configured, or the member has it, or the member
+/// can manage channels.
+pub fn is_dj(state: &State, guild_id: GuildId, member_id: UserId) -> bool {
+    let dj_role = match state.per_guild_data.get_dj_role(guild_id) {
+        Some(val) => val,
+        None => return true,
+    };
+
+    member_roles(state, guild_id, member_id).contains(&dj_role)
+        || can_manage_channels(state, guild_id, member_id)
+}
+
+pub fn can_manage_channels(state: &State, guild_id: GuildId, member_id: UserId) -> bool {
+    // The owner has all of the permissions regardless of the roles.
+    if state
+        .cache
+        .guild(guild_id)
+        .map(|guild| guild.owner_id == member_id)
+        .unwrap_or(false)
+    {
+        return true;
+    }
+
+    // The `@everyone` role has the same id as the guild.
+    let everyone_role = RoleId(guild_id.0);
+
+    member_roles(state, guild_id, member_id)
+        .into_iter()
+        .chain(std::iter::once(everyone_role))
+        .filter_map(|role_id| state.cache.role(role_id))
+        .any(|role| {
+            role.permissions
+                .intersects(Permissions::ADMINISTRATOR | Permissions::MANAGE_CHANNELS)
+        })
+}
+
+pub fn is_owner(state: &State, user_id: UserId) -> bool {
+    state.owner_ids.contains(&user_id)
+}
+
+/// Check whether the member is in the bot's voice channel, if the guild
+/// requires that and the bot is in one.
+pub fn in_bot_voice_channel(state: &State, guild_id: GuildId, member_id: UserId) -> bool {
+    if !state.per_guild_data.get_same_channel_required(guild_id) {
+        return true;
+    }
+    let bot_channel_id = match state.per_guild_data.get_voice_channel(guild_id) {
+        Some(val) => val,
+        None => return true,
+    };
+
+    state
+        .cache
+        .voice_state(member_id, guild_id)
+        .and_then(|voice_state| voice_state.channel_id)
+        == Some(bot_channel_id)
+}
+
+fn member_roles(state: &State, guild_id: GuildId, member_id: UserId) -> Vec<RoleId> {
+    state
+        .cache
+        .member(guild_id, member_id)
+        .map(|member| member.roles.clone())
+        .unwrap_or_default()
+}
@@ -0,0 +1,44 @@
+use crate::{per_guild_data::QueueSnapshot, State};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+/// How long to wait for a burst of changes to settle before writing.
+const WRITE_DEBOUNCE: Duration = Duration::from_secs(5);
+
+pub fn load(path: &Path) -> Result<Option<QueueSnapshot>, anyhow::Error> {
+    let data = match std::fs::read(path) {
+        Ok(val) => val,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(Some(serde_json::from_slice(&data)?))
+}
+
+pub async fn save(path: &Path, snapshot: &QueueSnapshot) -> Result<(), anyhow::Error> {
+    let data = serde_json::to_vec(snapshot)?;
+
+    // Write to a temporary file first so that a crash doesn't leave the state
+    // half-written.
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, data).await?;
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+pub async fn run(state: Arc<State>, path: PathBuf) {
+    loop {
+        state.per_guild_data.queues_changed().await;
+        tokio::time::sleep(WRITE_DEBOUNCE).await;
+
+        debug!(message = "saving queues", path = %path.display());
+        let snapshot = state.per_guild_data.snapshot();
+        if let Err(err) = save(&path, &snapshot).await {
+            warn!(message = "unable to save queues", ?err);
+        }
+    }
+}
@@ -1,19 +1,603 @@
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{vec_deque, HashSet, VecDeque},
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
 use twilight_lavalink::http::Track;
+use twilight_model::id::UserId;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    #[default]
+    Off,
+    Track,
+    Queue,
+}
+
+impl FromStr for LoopMode {
+    type Err = InvalidLoopMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "track" => Ok(Self::Track),
+            "queue" => Ok(Self::Queue),
+            _ => Err(InvalidLoopMode),
+        }
+    }
+}
+
+impl fmt::Display for LoopMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::Track => "track",
+            Self::Queue => "queue",
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("loop mode must be one of: off, track, queue")]
+pub struct InvalidLoopMode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BassPreset {
+    #[default]
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl BassPreset {
+    const BANDS: i64 = 15;
+
+    /// Gains for all of the equalizer bands, boosting the lowest ones.
+    pub fn bands(self) -> Vec<(i64, f64)> {
+        let boost = match self {
+            Self::Off => 0.0,
+            Self::Low => 0.1,
+            Self::Medium => 0.2,
+            Self::High => 0.3,
+        };
+        (0..Self::BANDS)
+            .map(|band| {
+                let gain = match band {
+                    0..=2 => boost,
+                    3 => boost / 2.0,
+                    _ => 0.0,
+                };
+                (band, gain)
+            })
+            .collect()
+    }
+}
+
+impl FromStr for BassPreset {
+    type Err = InvalidBassPreset;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            _ => Err(InvalidBassPreset),
+        }
+    }
+}
+
+impl fmt::Display for BassPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("bass boost preset must be one of: off, low, medium, high")]
+pub struct InvalidBassPreset;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FiltersPreset {
+    #[default]
+    Off,
+    Nightcore,
+    Vaporwave,
+}
+
+impl FiltersPreset {
+    /// The timescale filter as `(speed, pitch, rate)`.
+    pub fn timescale(self) -> (f64, f64, f64) {
+        match self {
+            Self::Off => (1.0, 1.0, 1.0),
+            Self::Nightcore => (1.2, 1.2, 1.0),
+            Self::Vaporwave => (0.8, 0.8, 1.0),
+        }
+    }
+}
+
+impl FromStr for FiltersPreset {
+    type Err = InvalidFiltersPreset;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "nightcore" => Ok(Self::Nightcore),
+            "vaporwave" => Ok(Self::Vaporwave),
+            _ => Err(InvalidFiltersPreset),
+        }
+    }
+}
+
+impl fmt::Display for FiltersPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::Nightcore => "nightcore",
+            Self::Vaporwave => "vaporwave",
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("filters preset must be one of: off, nightcore, vaporwave")]
+pub struct InvalidFiltersPreset;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchPlatform {
+    #[default]
+    YouTube,
+    SoundCloud,
+}
+
+impl SearchPlatform {
+    /// The identifier prefix Lavalink searches the platform with.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::YouTube => "ytsearch:",
+            Self::SoundCloud => "scsearch:",
+        }
+    }
+}
+
+impl FromStr for SearchPlatform {
+    type Err = InvalidSearchPlatform;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "youtube" => Ok(Self::YouTube),
+            "soundcloud" => Ok(Self::SoundCloud),
+            _ => Err(InvalidSearchPlatform),
+        }
+    }
+}
+
+impl fmt::Display for SearchPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::YouTube => "youtube",
+            Self::SoundCloud => "soundcloud",
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("search platform must be one of: youtube, soundcloud")]
+pub struct InvalidSearchPlatform;
+
+#[derive(Debug, Error)]
+#[error("no track at position {index}")]
+pub struct QueueIndexError {
+    pub index: usize,
+}
+
+#[derive(Debug, Error)]
+#[error("queue is full (max {max_len})")]
+pub struct QueueFull {
+    pub max_len: usize,
+}
+
+pub const DEFAULT_MAX_QUEUE_LEN: usize = 500;
+
+/// A track in the queue, along with the user who asked for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedTrack {
+    pub track: Track,
+    pub requested_by: UserId,
+}
+
+#[derive(Debug)]
 pub struct TrackManager {
-    track_queue: Vec<Track>,
+    track_queue: VecDeque<QueuedTrack>,
+    loop_mode: LoopMode,
+    max_len: usize,
+}
+
+impl Default for TrackManager {
+    fn default() -> Self {
+        Self::with_max_len(DEFAULT_MAX_QUEUE_LEN)
+    }
 }
 
 impl TrackManager {
-    pub fn enqueue<T>(&mut self, tracks: T)
+    pub fn with_max_len(max_len: usize) -> Self {
+        Self {
+            track_queue: Default::default(),
+            loop_mode: Default::default(),
+            max_len,
+        }
+    }
+
+    /// Add the tracks to the end of the queue, as many as fit in it.
+    /// Returns the number of the tracks that didn't fit.
+    pub fn enqueue<T>(&mut self, tracks: T) -> Result<usize, QueueFull>
     where
-        T: IntoIterator<Item = Track>,
+        T: IntoIterator<Item = QueuedTrack>,
     {
-        self.track_queue.extend(tracks)
+        let room = self.max_len.saturating_sub(self.track_queue.len());
+        if room == 0 {
+            return Err(QueueFull {
+                max_len: self.max_len,
+            });
+        }
+
+        let mut tracks = tracks.into_iter();
+        self.track_queue.extend(tracks.by_ref().take(room));
+        Ok(tracks.count())
+    }
+
+    pub fn enqueue_front(&mut self, track: QueuedTrack) {
+        self.track_queue.push_front(track)
+    }
+
+    pub fn next_track(&mut self) -> Option<QueuedTrack> {
+        self.track_queue.pop_front()
+    }
+
+    pub fn list(&self) -> vec_deque::Iter<'_, QueuedTrack> {
+        self.track_queue.iter()
+    }
+
+    pub fn loop_mode(&self) -> LoopMode {
+        self.loop_mode
+    }
+
+    pub fn set_loop_mode(&mut self, loop_mode: LoopMode) {
+        self.loop_mode = loop_mode;
+    }
+
+    /// Put the track that has just finished playing back into the queue
+    /// according to the loop mode.
+    pub fn requeue_finished(&mut self, track: QueuedTrack) {
+        match self.loop_mode {
+            LoopMode::Off => {}
+            LoopMode::Track => self.track_queue.push_front(track),
+            LoopMode::Queue => self.track_queue.push_back(track),
+        }
     }
 
-    pub fn next_track(&mut self) -> Option<Track> {
-        self.track_queue.pop()
+    pub fn shuffle(&mut self) -> usize {
+        self.shuffle_with(&mut rand::thread_rng())
+    }
+
+    pub fn shuffle_with<R: Rng + ?Sized>(&mut self, rng: &mut R) -> usize {
+        self.track_queue.make_contiguous().shuffle(rng);
+        self.track_queue.len()
+    }
+
+    /// Remove the track at the 1-based `index`, as displayed in the queue
+    /// listing.
+    pub fn remove(&mut self, index: usize) -> Option<QueuedTrack> {
+        self.track_queue.remove(index.checked_sub(1)?)
+    }
+
+    /// Move the track between the 1-based positions, as displayed in the
+    /// queue listing.
+    pub fn move_track(&mut self, from: usize, to: usize) -> Result<(), QueueIndexError> {
+        let from = self.checked_index(from)?;
+        let to = self.checked_index(to)?;
+        let track = self.track_queue.remove(from).unwrap();
+        self.track_queue.insert(to, track);
+        Ok(())
+    }
+
+    /// Swap the tracks at the 1-based positions.
+    pub fn swap(&mut self, a: usize, b: usize) -> Result<(), QueueIndexError> {
+        let a = self.checked_index(a)?;
+        let b = self.checked_index(b)?;
+        self.track_queue.swap(a, b);
+        Ok(())
+    }
+
+    /// Move the track at the 1-based `index` to the front, so it plays next.
+    pub fn bump(&mut self, index: usize) -> Option<QueuedTrack> {
+        let index = self.checked_index(index).ok()?;
+        let track = self.track_queue.remove(index)?;
+        self.track_queue.push_front(track.clone());
+        Some(track)
+    }
+
+    /// Remove and return the track at the 1-based `index`, dropping all the
+    /// tracks before it.
+    pub fn jump_to(&mut self, index: usize) -> Option<QueuedTrack> {
+        let index = self.checked_index(index).ok()?;
+        self.track_queue.drain(..index);
+        self.track_queue.pop_front()
+    }
+
+    /// Drop the tracks up to the first one matching the predicate and take
+    /// it, the queue is left untouched if none matches.
+    pub fn skip_to_match<P>(&mut self, predicate: P) -> Option<QueuedTrack>
+    where
+        P: FnMut(&QueuedTrack) -> bool,
+    {
+        let index = self.track_queue.iter().position(predicate)?;
+        self.track_queue.drain(..index);
+        self.track_queue.pop_front()
+    }
+
+    fn checked_index(&self, index: usize) -> Result<usize, QueueIndexError> {
+        if index == 0 || index > self.track_queue.len() {
+            return Err(QueueIndexError { index });
+        }
+        Ok(index - 1)
+    }
+
+    /// Remove the repeated occurrences of the tracks, keeping the first
+    /// ones. Returns the number of removed tracks.
+    pub fn dedup(&mut self) -> usize {
+        let len = self.track_queue.len();
+        let mut seen = HashSet::new();
+        self.track_queue
+            .retain(|track| seen.insert(track.track.info.identifier.clone()));
+        len - self.track_queue.len()
+    }
+
+    pub fn clear(&mut self) -> usize {
+        let removed = self.track_queue.len();
+        self.track_queue.clear();
+        removed
+    }
+}
+
+/// Watches the reported position of the playing track to tell when it stops
+/// moving.
+#[derive(Debug, Default)]
+pub struct PositionWatch {
+    last: Option<(i64, Instant)>,
+}
+
+impl PositionWatch {
+    /// Record the reported position, `None` when nothing is playing.
+    /// Returns `true` if the position hasn't moved for the `threshold`,
+    /// restarting the measurement.
+    pub fn observe(&mut self, position: Option<i64>, now: Instant, threshold: Duration) -> bool {
+        match (self.last, position) {
+            (Some((last, since)), Some(position)) if last == position => {
+                if now.duration_since(since) < threshold {
+                    return false;
+                }
+                self.last = Some((position, now));
+                true
+            }
+            (_, position) => {
+                self.last = position.map(|position| (position, now));
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(name: &str) -> QueuedTrack {
+        let track = serde_json::from_value(serde_json::json!({
+            "track": name,
+            "info": {
+                "author": "author",
+                "identifier": name,
+                "isSeekable": true,
+                "isStream": false,
+                "length": 1000,
+                "position": 0,
+                "title": name,
+                "uri": format!("https://example.com/{}", name),
+            },
+        }))
+        .unwrap();
+        QueuedTrack {
+            track,
+            requested_by: UserId::new(1).unwrap(),
+        }
+    }
+
+    #[test]
+    fn next_track_is_fifo() {
+        let mut track_manager = TrackManager::default();
+        track_manager
+            .enqueue(vec![track("a"), track("b"), track("c")])
+            .unwrap();
+
+        assert_eq!(track_manager.next_track(), Some(track("a")));
+        assert_eq!(track_manager.next_track(), Some(track("b")));
+        assert_eq!(track_manager.next_track(), Some(track("c")));
+        assert_eq!(track_manager.next_track(), None);
+    }
+
+    #[test]
+    fn enqueue_up_to_max_len() {
+        let mut track_manager = TrackManager::with_max_len(3);
+        track_manager.enqueue(vec![track("a")]).unwrap();
+
+        let skipped = track_manager
+            .enqueue(vec![track("b"), track("c"), track("d")])
+            .unwrap();
+        assert_eq!(skipped, 1);
+
+        let tracks: Vec<_> = track_manager.list().cloned().collect();
+        assert_eq!(tracks, vec![track("a"), track("b"), track("c")]);
+
+        assert!(track_manager.enqueue(vec![track("e")]).is_err());
+    }
+
+    #[test]
+    fn move_first_to_last() {
+        let mut track_manager = TrackManager::default();
+        track_manager
+            .enqueue(vec![track("a"), track("b"), track("c")])
+            .unwrap();
+
+        track_manager.move_track(1, 3).unwrap();
+
+        let tracks: Vec<_> = track_manager.list().cloned().collect();
+        assert_eq!(tracks, vec![track("b"), track("c"), track("a")]);
+    }
+
+    #[test]
+    fn move_out_of_bounds() {
+        let mut track_manager = TrackManager::default();
+        track_manager.enqueue(vec![track("a"), track("b")]).unwrap();
+
+        assert!(track_manager.move_track(0, 1).is_err());
+        assert!(track_manager.move_track(1, 3).is_err());
+
+        let tracks: Vec<_> = track_manager.list().cloned().collect();
+        assert_eq!(tracks, vec![track("a"), track("b")]);
+    }
+
+    #[test]
+    fn swap_first_and_last() {
+        let mut track_manager = TrackManager::default();
+        track_manager
+            .enqueue(vec![track("a"), track("b"), track("c"), track("d")])
+            .unwrap();
+
+        track_manager.swap(1, 4).unwrap();
+        assert!(track_manager.swap(1, 5).is_err());
+
+        let tracks: Vec<_> = track_manager.list().cloned().collect();
+        assert_eq!(tracks, vec![track("d"), track("b"), track("c"), track("a")]);
+    }
+
+    #[test]
+    fn bump_to_front() {
+        let mut track_manager = TrackManager::default();
+        track_manager
+            .enqueue(vec![track("a"), track("b"), track("c")])
+            .unwrap();
+
+        assert_eq!(track_manager.bump(3), Some(track("c")));
+        assert_eq!(track_manager.bump(4), None);
+
+        let tracks: Vec<_> = track_manager.list().cloned().collect();
+        assert_eq!(tracks, vec![track("c"), track("a"), track("b")]);
+    }
+
+    #[test]
+    fn shuffle_changes_order() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let tracks: Vec<_> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|name| track(name))
+            .collect();
+
+        let mut track_manager = TrackManager::default();
+        track_manager.enqueue(tracks.clone()).unwrap();
+
+        let mut rng = StdRng::seed_from_u64(42);
+        assert_eq!(track_manager.shuffle_with(&mut rng), tracks.len());
+
+        let shuffled: Vec<_> = track_manager.list().cloned().collect();
+        assert_ne!(shuffled, tracks);
+
+        let mut sorted = shuffled;
+        sorted.sort_by(|a, b| a.track.track.cmp(&b.track.track));
+        assert_eq!(sorted, tracks);
+    }
+
+    #[test]
+    fn parse_filters_preset() {
+        assert_eq!("off".parse::<FiltersPreset>().unwrap(), FiltersPreset::Off);
+        assert_eq!(
+            "nightcore".parse::<FiltersPreset>().unwrap(),
+            FiltersPreset::Nightcore
+        );
+        assert_eq!(
+            "vaporwave".parse::<FiltersPreset>().unwrap(),
+            FiltersPreset::Vaporwave
+        );
+        assert!("Nightcore".parse::<FiltersPreset>().is_err());
+        assert!("".parse::<FiltersPreset>().is_err());
+
+        for preset in [
+            FiltersPreset::Off,
+            FiltersPreset::Nightcore,
+            FiltersPreset::Vaporwave,
+        ] {
+            assert_eq!(preset.to_string().parse::<FiltersPreset>().unwrap(), preset);
+        }
+    }
+
+    #[test]
+    fn skip_to_the_matching_track() {
+        let mut track_manager = TrackManager::default();
+        track_manager
+            .enqueue(vec![track("a"), track("b"), track("c")])
+            .unwrap();
+
+        assert_eq!(
+            track_manager.skip_to_match(|track| track.track.info.identifier == "x"),
+            None
+        );
+        assert_eq!(track_manager.list().count(), 3);
+
+        assert_eq!(
+            track_manager.skip_to_match(|track| track.track.info.identifier == "b"),
+            Some(track("b"))
+        );
+        assert_eq!(
+            track_manager.list().cloned().collect::<Vec<_>>(),
+            vec![track("c")]
+        );
+    }
+
+    #[test]
+    fn position_watch_threshold() {
+        let threshold = Duration::from_secs(15);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut watch = PositionWatch::default();
+
+        assert!(!watch.observe(Some(1000), at(0), threshold));
+        assert!(!watch.observe(Some(1000), at(14), threshold));
+        assert!(watch.observe(Some(1000), at(15), threshold));
+        // The measurement restarts after reporting.
+        assert!(!watch.observe(Some(1000), at(29), threshold));
+        assert!(watch.observe(Some(1000), at(30), threshold));
+
+        // A moving position resets the measurement.
+        assert!(!watch.observe(Some(2000), at(40), threshold));
+        assert!(!watch.observe(Some(2000), at(54), threshold));
+        assert!(watch.observe(Some(2000), at(55), threshold));
+
+        // So does nothing playing.
+        assert!(!watch.observe(Some(3000), at(60), threshold));
+        assert!(!watch.observe(None, at(70), threshold));
+        assert!(!watch.observe(Some(3000), at(80), threshold));
+        assert!(!watch.observe(Some(3000), at(94), threshold));
+        assert!(watch.observe(Some(3000), at(95), threshold));
     }
 }
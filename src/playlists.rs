@@ -0,0 +1,209 @@
+use dashmap::DashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::PathBuf,
+};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use twilight_lavalink::http::Track;
+use twilight_model::id::GuildId;
+
+pub const DEFAULT_MAX_PLAYLISTS: usize = 25;
+
+/// The playlists of each guild, by name.
+type PlaylistsSnapshot = HashMap<GuildId, BTreeMap<String, Vec<Track>>>;
+
+/// The named playlists saved in the guilds, written to disk on every change
+/// if the path is set.
+#[derive(Debug)]
+pub struct Playlists {
+    path: Option<PathBuf>,
+    max_per_guild: usize,
+    map: DashMap<GuildId, BTreeMap<String, Vec<Track>>>,
+    write_lock: Mutex<()>,
+}
+
+impl Playlists {
+    pub fn load(path: Option<PathBuf>, max_per_guild: usize) -> Result<Self, anyhow::Error> {
+        let snapshot: PlaylistsSnapshot = match &path {
+            Some(path) => match std::fs::read(path) {
+                Ok(data) => serde_json::from_slice(&data)?,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Default::default(),
+                Err(err) => return Err(err.into()),
+            },
+            None => Default::default(),
+        };
+        Ok(Self {
+            path,
+            max_per_guild,
+            map: snapshot.into_iter().collect(),
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn get(&self, guild_id: GuildId, name: &str) -> Option<Vec<Track>> {
+        self.map.get(&guild_id)?.get(name).cloned()
+    }
+
+    /// The names of the guild's playlists along with their lengths, sorted
+    /// by name.
+    pub fn list(&self, guild_id: GuildId) -> Vec<(String, usize)> {
+        self.map
+            .get(&guild_id)
+            .map(|playlists| {
+                playlists
+                    .iter()
+                    .map(|(name, tracks)| (name.clone(), tracks.len()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub async fn save(
+        &self,
+        guild_id: GuildId,
+        name: String,
+        tracks: Vec<Track>,
+    ) -> Result<(), anyhow::Error> {
+        {
+            let mut playlists = self.map.entry(guild_id).or_default();
+            if playlists.contains_key(&name) {
+                return Err(PlaylistExists { name }.into());
+            }
+            if playlists.len() >= self.max_per_guild {
+                return Err(TooManyPlaylists {
+                    max: self.max_per_guild,
+                }
+                .into());
+            }
+            playlists.insert(name, tracks);
+        }
+        self.write().await
+    }
+
+    /// Delete the playlist, returns `false` if there was no such playlist.
+    pub async fn delete(&self, guild_id: GuildId, name: &str) -> Result<bool, anyhow::Error> {
+        let deleted = self
+            .map
+            .get_mut(&guild_id)
+            .map(|mut playlists| playlists.remove(name).is_some())
+            .unwrap_or(false);
+        if deleted {
+            self.write().await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn write(&self) -> Result<(), anyhow::Error> {
+        let path = match &self.path {
+            Some(val) => val,
+            None => return Ok(()),
+        };
+
+        // Keep the concurrent writes from mixing up the temporary file.
+        let _lock = self.write_lock.lock().await;
+        let snapshot: PlaylistsSnapshot = self
+            .map
+            .iter()
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect();
+        let data = serde_json::to_vec(&snapshot)?;
+
+        // Write to a temporary file first so that a crash doesn't leave the
+        // playlists half-written.
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, data).await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("playlist {name} already exists")]
+pub struct PlaylistExists {
+    pub name: String,
+}
+
+#[derive(Debug, Error)]
+#[error("too many playlists, at most {max} can be saved")]
+pub struct TooManyPlaylists {
+    pub max: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(name: &str) -> Track {
+        serde_json::from_value(serde_json::json!({
+            "track": name,
+            "info": {
+                "author": "author",
+                "identifier": name,
+                "isSeekable": true,
+                "isStream": false,
+                "length": 1000,
+                "position": 0,
+                "title": name,
+                "uri": format!("https://example.com/{}", name),
+            },
+        }))
+        .unwrap()
+    }
+
+    fn guild_id() -> GuildId {
+        GuildId::new(1).unwrap()
+    }
+
+    #[tokio::test]
+    async fn save_and_delete() {
+        let playlists = Playlists::load(None, DEFAULT_MAX_PLAYLISTS).unwrap();
+        playlists
+            .save(guild_id(), "mix".to_owned(), vec![track("a"), track("b")])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            playlists.get(guild_id(), "mix"),
+            Some(vec![track("a"), track("b")])
+        );
+        assert_eq!(playlists.list(guild_id()), vec![("mix".to_owned(), 2)]);
+
+        assert!(playlists.delete(guild_id(), "mix").await.unwrap());
+        assert!(!playlists.delete(guild_id(), "mix").await.unwrap());
+        assert_eq!(playlists.get(guild_id(), "mix"), None);
+    }
+
+    #[tokio::test]
+    async fn name_collision() {
+        let playlists = Playlists::load(None, DEFAULT_MAX_PLAYLISTS).unwrap();
+        playlists
+            .save(guild_id(), "mix".to_owned(), vec![track("a")])
+            .await
+            .unwrap();
+
+        let err = playlists
+            .save(guild_id(), "mix".to_owned(), vec![track("b")])
+            .await
+            .unwrap_err();
+        assert!(err.is::<PlaylistExists>());
+        assert_eq!(playlists.get(guild_id(), "mix"), Some(vec![track("a")]));
+    }
+
+    #[tokio::test]
+    async fn max_per_guild() {
+        let playlists = Playlists::load(None, 1).unwrap();
+        playlists
+            .save(guild_id(), "a".to_owned(), vec![track("a")])
+            .await
+            .unwrap();
+
+        let err = playlists
+            .save(guild_id(), "b".to_owned(), vec![track("b")])
+            .await
+            .unwrap_err();
+        assert!(err.is::<TooManyPlaylists>());
+    }
+}
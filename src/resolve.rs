@@ -0,0 +1,222 @@
+use crate::State;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const API_URL: &str = "https://api.spotify.com/v1";
+
+/// Spotify Web API client, authorized with the client credentials.
+#[derive(Debug)]
+pub struct Spotify {
+    client_id: String,
+    client_secret: String,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl Spotify {
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        Self {
+            client_id,
+            client_secret,
+            token: Mutex::new(None),
+        }
+    }
+
+    async fn token(&self, state: &State) -> Result<String, anyhow::Error> {
+        let mut token = self.token.lock().await;
+        if let Some((access_token, expires_at)) = &*token {
+            if Instant::now() < *expires_at {
+                return Ok(access_token.clone());
+            }
+        }
+
+        let res: TokenResponse = state
+            .reqwest
+            .post(TOKEN_URL)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // Refresh a bit early to not race the expiration.
+        let expires_at = Instant::now() + Duration::from_secs(res.expires_in.saturating_sub(60));
+        *token = Some((res.access_token.clone(), expires_at));
+        Ok(res.access_token)
+    }
+
+    async fn get<T>(&self, state: &State, url: &str) -> Result<T, anyhow::Error>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let token = self.token(state).await?;
+        let val = state
+            .reqwest
+            .get(url)
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(val)
+    }
+
+    /// Get the items of all the pages, following the `next` links until
+    /// there are `limit` items.
+    async fn get_all<T>(
+        &self,
+        state: &State,
+        url: &str,
+        limit: usize,
+    ) -> Result<Vec<T>, anyhow::Error>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let mut items = Vec::new();
+        let mut next = Some(url.to_owned());
+        while let Some(url) = next.take().filter(|_| items.len() < limit) {
+            let page: Page<T> = self.get(state, &url).await?;
+            items.extend(page.items);
+            next = page.next;
+        }
+        items.truncate(limit);
+        Ok(items)
+    }
+}
+
+/// Turn the identifier into the ones Lavalink can load. Spotify links are
+/// rewritten into searches, one per track of an album or a playlist, other
+/// identifiers are passed through as is.
+pub async fn resolve(state: &State, identifier: String) -> Result<Vec<String>, anyhow::Error> {
+    let (kind, id) = match parse_spotify_url(&identifier) {
+        Some(val) => val,
+        None => return Ok(vec![identifier]),
+    };
+    let spotify = state.spotify.as_ref().ok_or(SpotifyNotConfigured)?;
+
+    let limit = state.per_guild_data.max_queue_len();
+    let tracks = match kind {
+        "track" => vec![
+            spotify
+                .get(state, &format!("{}/tracks/{}", API_URL, id))
+                .await?,
+        ],
+        "album" => {
+            spotify
+                .get_all(
+                    state,
+                    &format!("{}/albums/{}/tracks?limit=50", API_URL, id),
+                    limit,
+                )
+                .await?
+        }
+        "playlist" => {
+            let items: Vec<PlaylistItem> = spotify
+                .get_all(
+                    state,
+                    &format!("{}/playlists/{}/tracks?limit=100", API_URL, id),
+                    limit,
+                )
+                .await?;
+            items.into_iter().filter_map(|item| item.track).collect()
+        }
+        _ => return Ok(vec![identifier]),
+    };
+
+    Ok(tracks.iter().map(search_identifier).collect())
+}
+
+fn search_identifier(track: &SpotifyTrack) -> String {
+    let artists: Vec<&str> = track
+        .artists
+        .iter()
+        .map(|artist| artist.name.as_str())
+        .collect();
+    format!("ytsearch:\"{} {}\"", track.name, artists.join(" "))
+}
+
+/// Extract the kind and the id from links like
+/// `https://open.spotify.com/track/<id>?si=...`.
+fn parse_spotify_url(url: &str) -> Option<(&str, &str)> {
+    let path = url
+        .strip_prefix("https://open.spotify.com/")
+        .or_else(|| url.strip_prefix("http://open.spotify.com/"))?;
+    let path = path.split(|c| c == '?' || c == '#').next()?;
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    // Skip the locale, as in `intl-de/track/<id>`.
+    let mut kind = segments.next()?;
+    if kind.starts_with("intl-") {
+        kind = segments.next()?;
+    }
+    let id = segments.next()?;
+    Some((kind, id))
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaylistItem {
+    track: Option<SpotifyTrack>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpotifyTrack {
+    name: String,
+    artists: Vec<SpotifyArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpotifyArtist {
+    name: String,
+}
+
+#[derive(Debug, Error)]
+#[error("spotify credentials are not configured")]
+pub struct SpotifyNotConfigured;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spotify_urls() {
+        assert_eq!(
+            parse_spotify_url("https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC"),
+            Some(("track", "4uLU6hMCjMI75M1A2tKUQC"))
+        );
+        assert_eq!(
+            parse_spotify_url("https://open.spotify.com/album/1DFixLWuPkv3KT3TnV35m3?si=abc"),
+            Some(("album", "1DFixLWuPkv3KT3TnV35m3"))
+        );
+        assert_eq!(
+            parse_spotify_url("http://open.spotify.com/intl-de/playlist/37i9dQZF1DX0XUsuxWHRQd#x"),
+            Some(("playlist", "37i9dQZF1DX0XUsuxWHRQd"))
+        );
+    }
+
+    #[test]
+    fn other_urls() {
+        assert_eq!(
+            parse_spotify_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+            None
+        );
+        assert_eq!(parse_spotify_url("https://open.spotify.com/"), None);
+        assert_eq!(parse_spotify_url("https://open.spotify.com/track"), None);
+        assert_eq!(parse_spotify_url("ytsearch:open.spotify.com"), None);
+    }
+}
@@ -1,33 +1,180 @@
-use crate::State;
-use std::sync::Arc;
+use crate::{action, commands, format_duration, lyrics, player, playlists, resolve, State};
+use std::{borrow::Cow, sync::Arc};
 use twilight_http::{request::prelude::CreateMessage, Response};
-use twilight_model::{channel::Message, id::ChannelId};
+use twilight_model::{
+    channel::{
+        embed::Embed,
+        message::{AllowedMentions, Message},
+    },
+    id::{ChannelId, MessageId},
+};
 
 #[derive(Debug, Clone)]
 pub struct ResponseContext {
     state: Arc<State>,
     channel_id: ChannelId,
+    message_id: MessageId,
+    mention: bool,
+    prefix: String,
 }
 
 impl ResponseContext {
-    pub fn new(state: Arc<State>, to: &Message) -> Self {
+    /// Create a context for replying to the `to` message; when `mention` is
+    /// `false` the reply still references the message, but doesn't ping its
+    /// author. The `prefix` is the one the guild uses, for the usage hints.
+    pub fn new(state: Arc<State>, to: &Message, mention: bool, prefix: String) -> Self {
         Self {
             state,
             channel_id: to.channel_id,
+            message_id: to.id,
+            mention,
+            prefix,
         }
     }
 
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Reply without pinging anyone, except for the author of the message
+    /// if the context was created with `mention`.
     pub async fn with<'msg, 's: 'msg, F>(&'s self, f: F) -> Result<Response<Message>, anyhow::Error>
     where
         F: FnOnce(CreateMessage<'msg>) -> Result<CreateMessage<'msg>, anyhow::Error>,
     {
-        let msg = self.state.http.create_message(self.channel_id);
+        self.send(f, self.mention).await
+    }
+
+    /// Reply pinging the author of the message regardless of the context
+    /// settings, nobody else is pinged still.
+    pub async fn with_reply_mention<'msg, 's: 'msg, F>(
+        &'s self,
+        f: F,
+    ) -> Result<Response<Message>, anyhow::Error>
+    where
+        F: FnOnce(CreateMessage<'msg>) -> Result<CreateMessage<'msg>, anyhow::Error>,
+    {
+        self.send(f, true).await
+    }
+
+    async fn send<'msg, 's: 'msg, F>(
+        &'s self,
+        f: F,
+        replied_user: bool,
+    ) -> Result<Response<Message>, anyhow::Error>
+    where
+        F: FnOnce(CreateMessage<'msg>) -> Result<CreateMessage<'msg>, anyhow::Error>,
+    {
+        let msg = self
+            .state
+            .http
+            .create_message(self.channel_id)
+            .reply(self.message_id)
+            .allowed_mentions(AllowedMentions {
+                replied_user,
+                ..Default::default()
+            });
         let msg = f(msg)?;
         let val = msg.exec().await?;
         Ok(val)
     }
 
     pub async fn with_content(&self, content: &str) -> Result<Response<Message>, anyhow::Error> {
-        self.with(|msg| Ok(msg.content(content)?)).await
+        let content = truncate_content(content);
+        self.with(|msg| Ok(msg.content(&content)?)).await
+    }
+
+    /// Reply with the content followed by how to use the command.
+    pub async fn with_usage(
+        &self,
+        content: &str,
+        command: &str,
+    ) -> Result<Response<Message>, anyhow::Error> {
+        let content = format!(
+            "{}, like `{}`",
+            content,
+            commands::usage(&self.prefix, command)
+        );
+        self.with_content(&content).await
+    }
+
+    pub async fn with_embed(&self, embed: Embed) -> Result<Response<Message>, anyhow::Error> {
+        let embeds = [embed];
+        self.with(|msg| Ok(msg.embeds(&embeds)?)).await
+    }
+}
+
+/// The maximum length of the message content, in characters.
+const MESSAGE_CONTENT_LIMIT: usize = 2000;
+
+/// Cut the content to fit into a message, marking the cut with an ellipsis.
+pub fn truncate_content(content: &str) -> Cow<'_, str> {
+    if content.chars().count() <= MESSAGE_CONTENT_LIMIT {
+        return Cow::Borrowed(content);
+    }
+    let mut truncated: String = content.chars().take(MESSAGE_CONTENT_LIMIT - 1).collect();
+    truncated.push('…');
+    Cow::Owned(truncated)
+}
+
+/// The reply for the errors caused by the user or the setup rather than by a
+/// bug, `None` for the unexpected errors.
+pub fn error_content(err: &anyhow::Error) -> Option<String> {
+    let content = if err.is::<action::NoLavalinkNode>() {
+        "Music backend is unavailable, try again shortly".to_owned()
+    } else if err.is::<action::LavalinkTimeout>() {
+        "Music backend timed out".to_owned()
+    } else if let Some(err) = err.downcast_ref::<player::QueueFull>() {
+        format!("Queue is full (max {})", err.max_len)
+    } else if err.is::<action::NoTracksFound>() {
+        "No tracks found".to_owned()
+    } else if err.is::<action::NoMatches>() {
+        "No matches found".to_owned()
+    } else if let Some(err) = err.downcast_ref::<action::LoadFailed>() {
+        format!("Lavalink failed to load: {}", err.message)
+    } else if let Some(err) = err.downcast_ref::<action::RangeOutOfBounds>() {
+        format!(
+            "Positions must be within the track length of {}, start before end",
+            format_duration(err.length)
+        )
+    } else if err.is::<action::RangeWhileEnqueuing>() {
+        "Positions can only be used when nothing is playing".to_owned()
+    } else if err.is::<action::NothingPlaying>() {
+        "Nothing is playing".to_owned()
+    } else if err.is::<action::NotConnected>() {
+        "I'm not in a voice channel".to_owned()
+    } else if err.is::<action::AlreadyPaused>() {
+        "Already paused".to_owned()
+    } else if err.is::<action::AlreadyPlaying>() {
+        "Already playing".to_owned()
+    } else if let Some(err) = err.downcast_ref::<action::VolumeValueOutOfBounds>() {
+        format!("Invalid volume value: {}", err)
+    } else if err.is::<resolve::SpotifyNotConfigured>() {
+        "Spotify links are not supported, the Spotify credentials are not configured".to_owned()
+    } else if err.is::<lyrics::LyricsNotConfigured>() {
+        "Lyrics are not supported, the lyrics provider is not configured".to_owned()
+    } else if let Some(err) = err.downcast_ref::<playlists::PlaylistExists>() {
+        format!("Playlist {} already exists, delete it first", err.name)
+    } else if let Some(err) = err.downcast_ref::<playlists::TooManyPlaylists>() {
+        format!("Too many playlists (max {}), delete some first", err.max)
+    } else {
+        return None;
+    };
+    Some(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_content_at_limit() {
+        let content = "a".repeat(MESSAGE_CONTENT_LIMIT);
+        assert_eq!(truncate_content(&content), content);
+
+        let content = "a".repeat(MESSAGE_CONTENT_LIMIT + 1);
+        let truncated = truncate_content(&content);
+        assert_eq!(truncated.chars().count(), MESSAGE_CONTENT_LIMIT);
+        assert!(truncated.ends_with('…'));
     }
 }
@@ -1,11 +1,18 @@
+use dashmap::DashMap;
 use reqwest::Client as ReqwestClient;
+use std::{collections::HashMap, fmt, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::{Mutex, OwnedMutexGuard};
 use twilight_cache_inmemory::InMemoryCache;
 use twilight_gateway::Shard;
 use twilight_http::Client as HttpClient;
-use twilight_lavalink::Lavalink;
+use twilight_lavalink::{model::Stats, Lavalink};
+use twilight_model::id::{GuildId, MessageId, UserId};
 use twilight_standby::Standby;
 
-use crate::per_guild_data::Store;
+use crate::{
+    cooldowns::Cooldowns, lyrics::Lyrics, metrics::Metrics, per_guild_data::Store,
+    playlists::Playlists, resolve::Spotify, track_cache::TrackCache,
+};
 
 #[derive(Debug)]
 pub struct State {
@@ -15,6 +22,56 @@ pub struct State {
     pub shard: Shard,
     pub standby: Standby,
     pub cache: InMemoryCache,
-    pub command_prefix: String,
     pub per_guild_data: Store,
+    pub reply_mention: bool,
+    pub user_id: UserId,
+    pub empty_channel_timeout: Duration,
+    pub idle_timeout: Duration,
+    pub idle_warning: Option<Duration>,
+    pub queue_state_path: Option<PathBuf>,
+    pub metrics: Metrics,
+    pub track_cache: TrackCache,
+    pub fade_duration: Option<Duration>,
+    pub node_stats: DashMap<LavalinkHost, Stats>,
+    pub progress_update_interval: Option<Duration>,
+    pub spotify: Option<Spotify>,
+    pub lavalink_hosts: Vec<LavalinkHost>,
+    /// The names of the named Lavalink nodes, by address.
+    pub lavalink_names: HashMap<SocketAddr, String>,
+    pub lavalink_authorization: String,
+    pub owner_ids: Vec<UserId>,
+    pub cooldowns: Cooldowns,
+    pub lyrics: Option<Lyrics>,
+    pub queue_listings: DashMap<MessageId, (GuildId, usize)>,
+    pub stop_confirmation_threshold: usize,
+    /// Taken by the commands starting, skipping or stopping the playback or
+    /// editing the queue, and by the track end handling, so that the
+    /// changes within a guild don't interleave.
+    pub guild_locks: DashMap<GuildId, Arc<Mutex<()>>>,
+    pub playlists: Playlists,
+}
+
+impl State {
+    /// Wait for the other changes to the guild's player and queue to finish.
+    pub async fn lock_guild(&self, guild_id: GuildId) -> OwnedMutexGuard<()> {
+        let lock = Arc::clone(&self.guild_locks.entry(guild_id).or_default());
+        lock.lock_owned().await
+    }
+}
+
+/// A Lavalink node from the config, optionally named to tell the nodes
+/// apart in the logs and stats.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LavalinkHost {
+    pub name: Option<String>,
+    pub address: SocketAddr,
+}
+
+impl fmt::Display for LavalinkHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} ({})", name, self.address),
+            None => write!(f, "{}", self.address),
+        }
+    }
 }
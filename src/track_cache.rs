@@ -0,0 +1,73 @@
+use lru::LruCache;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use twilight_lavalink::http::LoadedTracks;
+
+/// Recent `/loadtracks` results, keyed by the identifier.
+#[derive(Debug)]
+pub struct TrackCache {
+    entries: Mutex<LruCache<String, (Instant, LoadedTracks)>>,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub len: usize,
+    pub capacity: usize,
+}
+
+impl TrackCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, identifier: &str) -> Option<LoadedTracks> {
+        let mut entries = self.entries.lock().unwrap();
+        let fresh = match entries.get(identifier) {
+            Some((loaded_at, loaded)) if loaded_at.elapsed() < self.ttl => Some(loaded.clone()),
+            Some(_) => {
+                entries.pop(identifier);
+                None
+            }
+            None => None,
+        };
+        let counter = if fresh.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        fresh
+    }
+
+    pub fn insert(&self, identifier: String, loaded: LoadedTracks) {
+        self.entries
+            .lock()
+            .unwrap()
+            .put(identifier, (Instant::now(), loaded));
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            len: entries.len(),
+            capacity: entries.cap(),
+        }
+    }
+}
@@ -1,7 +1,11 @@
+use crate::{action, spawn, State};
+use std::sync::Arc;
+use tracing::info;
 use twilight_gateway::{shard::CommandError, Shard};
 use twilight_model::{
     gateway::payload::outgoing::UpdateVoiceState,
     id::{ChannelId, GuildId},
+    voice::VoiceState,
 };
 
 pub async fn join(
@@ -24,3 +28,62 @@ pub async fn leave(shard: &Shard, guild_id: impl Into<GuildId>) -> Result<(), Co
         .command(&UpdateVoiceState::new(guild_id, None, false, false))
         .await
 }
+
+/// Keep track of the voice channel the bot is in, noticing when it is moved
+/// or disconnected by someone else.
+///
+/// The voice connection itself is handed over to Lavalink when the event is
+/// processed by the Lavalink client, so the playback continues in the new
+/// channel.
+pub fn process_voice_state_update(state: &State, voice_state: &VoiceState) {
+    if voice_state.user_id != state.user_id {
+        return;
+    }
+    let guild_id = match voice_state.guild_id {
+        Some(val) => val,
+        None => return,
+    };
+
+    let previous = state
+        .per_guild_data
+        .set_voice_channel(guild_id, voice_state.channel_id);
+    match (previous, voice_state.channel_id) {
+        (Some(previous), Some(current)) if previous != current => {
+            info!(message = "bot was moved to another voice channel", %guild_id, %previous, %current);
+        }
+        (Some(previous), None) => {
+            info!(message = "bot was disconnected from the voice channel", %guild_id, %previous);
+        }
+        _ => {}
+    }
+}
+
+/// Move along with the user who played a track last, if the guild has the
+/// following turned on and something is playing.
+pub fn follow_controller(state: &Arc<State>, voice_state: &VoiceState) {
+    let guild_id = match voice_state.guild_id {
+        Some(val) => val,
+        None => return,
+    };
+    if !state.per_guild_data.get_follow(guild_id)
+        || state.per_guild_data.get_controller(guild_id) != Some(voice_state.user_id)
+        || state.per_guild_data.current_track(guild_id).is_none()
+    {
+        return;
+    }
+
+    let (current, channel_id) = match (
+        state.per_guild_data.get_voice_channel(guild_id),
+        voice_state.channel_id,
+    ) {
+        (Some(current), Some(channel_id)) => (current, channel_id),
+        _ => return,
+    };
+    if current == channel_id {
+        return;
+    }
+
+    info!(message = "following the controlling user", %guild_id, %current, %channel_id);
+    let state = Arc::clone(state);
+    spawn(async move { action::move_to(&state, guild_id, channel_id).await });
+}